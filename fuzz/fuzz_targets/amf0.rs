@@ -0,0 +1,15 @@
+#![no_main]
+
+use flvp::tag::{amf0, amf3};
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let _ = amf0::amf_data(data);
+    let _ = amf0::script_data(data);
+    let _ = amf0::amf_data_value(data);
+    let _ = amf0::amf_objects(data);
+    let _ = amf0::amf_ecma_array(data);
+    let _ = amf0::amf_strict_array(data);
+    let _ = amf0::amf_typed_object(data);
+    let _ = amf3::amf3_value(data);
+});
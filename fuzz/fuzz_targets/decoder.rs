@@ -0,0 +1,22 @@
+#![no_main]
+
+use flvp::decoder::{Decoded, FlvDecoder};
+use libfuzzer_sys::fuzz_target;
+
+// The first byte picks the chunk size the rest of the input is pushed in
+fuzz_target!(|data: &[u8]| {
+    let Some((&chunk, data)) = data.split_first() else {
+        return;
+    };
+    let mut decoder = FlvDecoder::new();
+    for bytes in data.chunks(chunk as usize + 1) {
+        decoder.push(bytes);
+        loop {
+            match decoder.poll() {
+                Ok(Decoded::NeedMore(_)) => break,
+                Ok(_) => {},
+                Err(_) => return,
+            }
+        }
+    }
+});
@@ -0,0 +1,14 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let _ = flvp::file::flv_file(data);
+    if let Ok(reader) = flvp::reader::FlvReader::new(data) {
+        for tag in reader {
+            if tag.is_err() {
+                break;
+            }
+        }
+    }
+});
@@ -0,0 +1,28 @@
+#![no_main]
+
+use flvp::tag;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let _ = tag::tag_header(data);
+    let _ = tag::tag(data);
+    if let Some((&size, data)) = data.split_first() {
+        let size = size as usize;
+        let _ = tag::audio::audio_data(data, size);
+        let _ = tag::audio::aac_audio_packet(data, size);
+        let _ = tag::video::video_data(data, size);
+        let _ = tag::video::avc_video_packet(data, size);
+        let _ = tag::encryption::encrypted_data(data, size as u8 & 0b1_1111);
+        let _ = tag::audio::adpcm::decode_adpcm(data, size % 2 + 1);
+    }
+    let _ = tag::video::avc::avc_decoder_configuration_record(data);
+    let _ = tag::video::sps::sequence_parameter_set(data);
+    let _ = tag::video::hevc::hevc_decoder_configuration_record(data);
+    let _ = tag::video::av1::av1_codec_configuration_record(data);
+    let _ = tag::video::vp9::vp_codec_configuration_record(data);
+    let _ = tag::audio::aac::audio_specific_config(data);
+    let _ = tag::audio::mp3::mpeg_audio_frame_header(data);
+    let _ = tag::audio::opus::opus_head(data);
+    let _ = tag::audio::flac::flac_stream_info(data);
+    let _ = tag::audio::multichannel_config(data);
+});
@@ -0,0 +1,74 @@
+use crate::Error;
+
+// Reads big endian bit fields, most significant bit first.
+// Errors are reported at the offset of the byte holding the missing bit.
+pub(crate) struct BitReader<'a> {
+    data: &'a [u8],
+    // position in bits
+    pos: usize,
+}
+
+impl<'a> BitReader<'a> {
+    pub(crate) fn new(data: &'a [u8]) -> Self {
+        BitReader { data, pos: 0 }
+    }
+
+    pub(crate) fn bits_left(&self) -> usize {
+        self.data.len() * 8 - self.pos
+    }
+
+    // Offset of the byte holding the next bit
+    pub(crate) fn byte_offset(&self) -> usize {
+        self.pos / 8
+    }
+
+    pub(crate) fn read_bit(&mut self) -> Result<bool, Error> {
+        let byte = self.pos / 8;
+        match self.data.get(byte) {
+            Some(b) => {
+                let bit = b >> (7 - self.pos % 8) & 1;
+                self.pos += 1;
+                Ok(bit == 1)
+            },
+            None => Err(Error::Truncated { needed: 1, offset: byte }),
+        }
+    }
+
+    // Up to 32 bits
+    pub(crate) fn read_bits(&mut self, n: u32) -> Result<u32, Error> {
+        debug_assert!(n <= 32);
+        let mut res: u32 = 0;
+        for _ in 0..n {
+            res = res << 1 | self.read_bit()? as u32;
+        }
+        Ok(res)
+    }
+
+    pub(crate) fn skip_bits(&mut self, n: usize) -> Result<(), Error> {
+        if self.bits_left() < n {
+            return Err(Error::Truncated { needed: n.div_ceil(8), offset: self.pos / 8 });
+        }
+        self.pos += n;
+        Ok(())
+    }
+
+    // Unsigned exp-Golomb code, ue(v) in H.264
+    pub(crate) fn read_ue(&mut self) -> Result<u32, Error> {
+        let mut leading_zeros = 0;
+        while !self.read_bit()? {
+            leading_zeros += 1;
+            if leading_zeros > 31 {
+                return Err(Error::InvalidExpGolomb { offset: self.pos / 8 });
+            }
+        }
+        let rest = self.read_bits(leading_zeros)?;
+        Ok(((1u64 << leading_zeros) - 1 + rest as u64) as u32)
+    }
+
+    // Signed exp-Golomb code, se(v) in H.264
+    pub(crate) fn read_se(&mut self) -> Result<i32, Error> {
+        let k = self.read_ue()? as i64;
+        let res = if k % 2 == 1 { (k + 1) / 2 } else { -(k / 2) };
+        Ok(res as i32)
+    }
+}
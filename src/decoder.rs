@@ -0,0 +1,150 @@
+use crate::file::PREVIOUS_TAG_SIZE;
+use crate::header::{self, FLVHeader, FLV_HEADER_SIZE};
+use crate::tag::{self, Tag, UnknownTagPolicy, TAG_HEADER_SIZE};
+use crate::Error;
+
+// What a call to `FlvDecoder::poll` produced
+#[derive(Debug, PartialEq)]
+pub enum Decoded {
+    // The FLV header, always the first thing decoded
+    Header(FLVHeader),
+    // A complete tag
+    Tag(Tag),
+    // At least this many more bytes must be pushed before anything else can be decoded
+    NeedMore(usize),
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+enum State {
+    Header,
+    // bytes left between the end of the FLV header and `data_offset`
+    HeaderPadding(usize),
+    PreviousTagSize,
+    Tag,
+}
+
+// Push-based decoder for FLV bytes arriving in arbitrary chunks.
+// Feed bytes with `push`, then call `poll` until it returns `Decoded::NeedMore`.
+// It never blocks and never looks at a tag before all of its bytes are buffered.
+// Error offsets are counted from the first byte pushed.
+pub struct FlvDecoder {
+    state: State,
+    buf: Vec<u8>,
+    // start of the bytes not yet consumed in `buf`
+    pos: usize,
+    // number of bytes consumed before `buf[0]`
+    base: usize,
+    header: Option<FLVHeader>,
+    unknown_tags: UnknownTagPolicy,
+}
+
+impl Default for FlvDecoder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl FlvDecoder {
+    pub fn new() -> Self {
+        FlvDecoder {
+            state: State::Header,
+            buf: Vec::new(),
+            pos: 0,
+            base: 0,
+            header: None,
+            unknown_tags: UnknownTagPolicy::default(),
+        }
+    }
+
+    // The FLV header, once it has been decoded
+    pub fn header(&self) -> Option<&FLVHeader> {
+        self.header.as_ref()
+    }
+
+    // Unknown tags are kept by default
+    pub fn set_unknown_tag_policy(&mut self, policy: UnknownTagPolicy) {
+        self.unknown_tags = policy;
+    }
+
+    // Number of bytes pushed but not decoded yet
+    pub fn buffered(&self) -> usize {
+        self.buf.len() - self.pos
+    }
+
+    // Number of bytes consumed so far
+    pub fn position(&self) -> usize {
+        self.base + self.pos
+    }
+
+    pub fn push(&mut self, data: &[u8]) {
+        if self.pos > 0 {
+            self.buf.drain(..self.pos);
+            self.base += self.pos;
+            self.pos = 0;
+        }
+        self.buf.extend_from_slice(data);
+    }
+
+    // Decodes the next item from the buffered bytes.
+    // A tag that fails to parse is still consumed, so decoding can carry on with the next one.
+    pub fn poll(&mut self) -> Result<Decoded, Error> {
+        loop {
+            let position = self.position();
+            let input = &self.buf[self.pos..];
+            match self.state {
+                State::Header => {
+                    if input.len() < FLV_HEADER_SIZE {
+                        return Ok(Decoded::NeedMore(FLV_HEADER_SIZE - input.len()));
+                    }
+                    let header = header::flv_header(&input[..FLV_HEADER_SIZE]).map_err(|e| e.shift(position))?;
+                    self.pos += FLV_HEADER_SIZE;
+                    let padding = (header.data_offset as usize).saturating_sub(FLV_HEADER_SIZE);
+                    self.state = State::HeaderPadding(padding);
+                    self.header = Some(header.clone());
+                    return Ok(Decoded::Header(header));
+                },
+                State::HeaderPadding(left) => {
+                    let n = left.min(input.len());
+                    self.pos += n;
+                    if n < left {
+                        self.state = State::HeaderPadding(left - n);
+                        return Ok(Decoded::NeedMore(left - n));
+                    }
+                    self.state = State::PreviousTagSize;
+                },
+                State::PreviousTagSize => {
+                    if input.len() < PREVIOUS_TAG_SIZE {
+                        return Ok(Decoded::NeedMore(PREVIOUS_TAG_SIZE - input.len()));
+                    }
+                    self.pos += PREVIOUS_TAG_SIZE;
+                    self.state = State::Tag;
+                },
+                State::Tag => {
+                    if input.len() < TAG_HEADER_SIZE {
+                        return Ok(Decoded::NeedMore(TAG_HEADER_SIZE - input.len()));
+                    }
+                    let header = tag::tag_header(&input[..TAG_HEADER_SIZE]).map_err(|e| e.shift(position))?;
+                    let tag_size = TAG_HEADER_SIZE + header.data_size as usize;
+                    if input.len() < tag_size {
+                        return Ok(Decoded::NeedMore(tag_size - input.len()));
+                    }
+                    let known = tag::is_known_tag_type(header.tag_type);
+                    if !known && self.unknown_tags == UnknownTagPolicy::Skip {
+                        self.pos += tag_size;
+                        self.state = State::PreviousTagSize;
+                        continue;
+                    }
+                    let res = if !known && self.unknown_tags == UnknownTagPolicy::Error {
+                        Err(Error::UnknownTagType { tag_type: header.tag_type, offset: 0 })
+                    } else {
+                        tag::tag(&input[..tag_size])
+                    };
+                    self.pos += tag_size;
+                    self.state = State::PreviousTagSize;
+                    let (tag, _) = res.map_err(|e| e.shift(position))?;
+                    return Ok(Decoded::Tag(tag));
+                },
+            }
+        }
+    }
+}
@@ -0,0 +1,164 @@
+use std::fmt;
+use std::str::Utf8Error;
+
+// Every offset is in bytes. Parsers report it relative to the input they were given,
+// and the file level readers turn it into an absolute offset from the start of the file.
+#[derive(Debug, PartialEq)]
+pub enum Error {
+    // A field of `needed` bytes starting at `offset` runs past the end of input
+    Truncated { needed: usize, offset: usize },
+    // The input does not start with `what`: 'FLV' for a file, the sync of an MPEG audio frame
+    // or 'OpusHead'
+    BadSignature { what: &'static str, offset: usize },
+    InvalidFrameType { frame_type: u8, offset: usize },
+    InvalidCodecId { codec_id: u8, offset: usize },
+    InvalidAvcPacketType { packet_type: u8, offset: usize },
+    InvalidAacPacketType { packet_type: u8, offset: usize },
+    InvalidVideoPacketType { packet_type: u8, offset: usize },
+    InvalidAudioPacketType { packet_type: u8, offset: usize },
+    InvalidAmfMarker { marker: u8, offset: usize },
+    // An AMF3 reference to a string, traits or object not read yet
+    InvalidAmfReference { index: usize, offset: usize },
+    // A tag of a reserved type, under `UnknownTagPolicy::Error`
+    UnknownTagType { tag_type: u8, offset: usize },
+    // An exp-Golomb code longer than 32 bits
+    InvalidExpGolomb { offset: usize },
+    // AMF objects or arrays nested deeper than `amf0::AMF_MAX_DEPTH`
+    NestingTooDeep { offset: usize },
+    // Well formed, but not something this crate can decode
+    Unsupported { what: &'static str, offset: usize },
+    Utf8 { source: Utf8Error, offset: usize },
+    // A value of `size` bytes or items too large to be encoded
+    TooLarge { what: &'static str, size: usize },
+    Io(IoError),
+}
+
+// io::Error is not PartialEq: two of them compare equal when their kinds match
+#[derive(Debug)]
+pub struct IoError(pub std::io::Error);
+
+impl PartialEq for IoError {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.kind() == other.0.kind()
+    }
+}
+
+impl Error {
+    // Where the error occurred, if it is about the input bytes
+    pub fn offset(&self) -> Option<usize> {
+        match *self {
+            Error::Truncated { offset, .. }
+            | Error::BadSignature { offset, .. }
+            | Error::InvalidFrameType { offset, .. }
+            | Error::InvalidCodecId { offset, .. }
+            | Error::InvalidAvcPacketType { offset, .. }
+            | Error::InvalidAacPacketType { offset, .. }
+            | Error::InvalidVideoPacketType { offset, .. }
+            | Error::InvalidAudioPacketType { offset, .. }
+            | Error::InvalidAmfMarker { offset, .. }
+            | Error::InvalidAmfReference { offset, .. }
+            | Error::UnknownTagType { offset, .. }
+            | Error::InvalidExpGolomb { offset }
+            | Error::NestingTooDeep { offset }
+            | Error::Unsupported { offset, .. }
+            | Error::Utf8 { offset, .. } => Some(offset),
+            Error::TooLarge { .. } | Error::Io(_) => None,
+        }
+    }
+
+    // Moves the offset forward by `n`, for errors from a parser run on `&input[n..]`
+    pub(crate) fn shift(mut self, n: usize) -> Self {
+        match &mut self {
+            Error::Truncated { offset, .. }
+            | Error::BadSignature { offset, .. }
+            | Error::InvalidFrameType { offset, .. }
+            | Error::InvalidCodecId { offset, .. }
+            | Error::InvalidAvcPacketType { offset, .. }
+            | Error::InvalidAacPacketType { offset, .. }
+            | Error::InvalidVideoPacketType { offset, .. }
+            | Error::InvalidAudioPacketType { offset, .. }
+            | Error::InvalidAmfMarker { offset, .. }
+            | Error::InvalidAmfReference { offset, .. }
+            | Error::UnknownTagType { offset, .. }
+            | Error::InvalidExpGolomb { offset }
+            | Error::NestingTooDeep { offset }
+            | Error::Unsupported { offset, .. }
+            | Error::Utf8 { offset, .. } => *offset += n,
+            Error::TooLarge { .. } | Error::Io(_) => {},
+        }
+        self
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Truncated { needed, offset } => {
+                write!(f, "truncated input: {} bytes needed at offset {}", needed, offset)
+            },
+            Error::BadSignature { what, offset } => write!(f, "bad {} at offset {}", what, offset),
+            Error::InvalidFrameType { frame_type, offset } => {
+                write!(f, "invalid video frame type {} at offset {}", frame_type, offset)
+            },
+            Error::InvalidCodecId { codec_id, offset } => {
+                write!(f, "invalid video codec id {} at offset {}", codec_id, offset)
+            },
+            Error::InvalidAvcPacketType { packet_type, offset } => {
+                write!(f, "invalid AVC packet type {} at offset {}", packet_type, offset)
+            },
+            Error::InvalidAacPacketType { packet_type, offset } => {
+                write!(f, "invalid AAC packet type {} at offset {}", packet_type, offset)
+            },
+            Error::InvalidVideoPacketType { packet_type, offset } => {
+                write!(f, "invalid video packet type {} at offset {}", packet_type, offset)
+            },
+            Error::InvalidAudioPacketType { packet_type, offset } => {
+                write!(f, "invalid audio packet type {} at offset {}", packet_type, offset)
+            },
+            Error::InvalidAmfMarker { marker, offset } => {
+                write!(f, "invalid AMF marker {:#04x} at offset {}", marker, offset)
+            },
+            Error::InvalidAmfReference { index, offset } => {
+                write!(f, "invalid AMF reference {} at offset {}", index, offset)
+            },
+            Error::UnknownTagType { tag_type, offset } => {
+                write!(f, "unknown tag type {} at offset {}", tag_type, offset)
+            },
+            Error::InvalidExpGolomb { offset } => write!(f, "invalid exp-Golomb code at offset {}", offset),
+            Error::NestingTooDeep { offset } => write!(f, "AMF values nested too deep at offset {}", offset),
+            Error::Unsupported { what, offset } => write!(f, "unsupported {} at offset {}", what, offset),
+            Error::Utf8 { source, offset } => write!(f, "invalid UTF-8 at offset {}: {}", offset, source),
+            Error::TooLarge { what, size } => write!(f, "{} too large to encode: {}", what, size),
+            Error::Io(e) => write!(f, "io error: {}", e.0),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Utf8 { source, .. } => Some(source),
+            Error::Io(e) => Some(&e.0),
+            _ => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for Error {
+    fn from(e: std::io::Error) -> Self {
+        Error::Io(IoError(e))
+    }
+}
+
+// Number of bytes a parser consumed, given its input and the input it left
+pub(crate) fn consumed(input: &[u8], last: &[u8]) -> usize {
+    input.len() - last.len()
+}
+
+// Splits `n` bytes off the front of `input`
+pub(crate) fn take(input: &[u8], n: usize) -> Result<(&[u8], &[u8]), Error> {
+    if input.len() < n {
+        return Err(Error::Truncated { needed: n, offset: 0 });
+    }
+    Ok(input.split_at(n))
+}
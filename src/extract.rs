@@ -0,0 +1,6 @@
+// Extractors turn the audio or video tags of an FLV file into a standalone elementary stream.
+// Each one is fed tags in file order and ignores the tags it does not handle.
+pub mod adts;
+pub mod h264;
+pub mod mp3;
+pub mod wav;
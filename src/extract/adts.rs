@@ -0,0 +1,71 @@
+use std::io::{Read, Write};
+
+use crate::reader::FlvReader;
+use crate::tag::audio::aac::AudioSpecificConfig;
+use crate::tag::audio::{AACAudioData, AACPacketType, SoundData};
+use crate::tag::{Tag, TagData};
+use crate::Error;
+
+// Writes the AAC audio of an FLV file as an ADTS stream, the usual `.aac` file:
+// every raw frame is prefixed with an ADTS header derived from the last sequence header,
+// so a change of configuration mid-stream carries over to the following frames.
+pub struct AdtsExtractor<W: Write> {
+    inner: W,
+    config: Option<AudioSpecificConfig>,
+}
+
+impl<W: Write> AdtsExtractor<W> {
+    pub fn new(inner: W) -> Self {
+        AdtsExtractor {
+            inner,
+            config: None,
+        }
+    }
+
+    // Frames before the first sequence header cannot be decoded and are dropped
+    pub fn write_tag(&mut self, tag: &Tag) -> Result<(), Error> {
+        if let TagData::Audio(audio) = &tag.data {
+            if let SoundData::AAC(packet) = &audio.sound_data {
+                self.write_packet(packet)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn write_packet(&mut self, packet: &AACAudioData) -> Result<(), Error> {
+        let config = match packet.aac_packet_type {
+            AACPacketType::SequenceHeader => {
+                self.config = packet.audio_specific_config().transpose()?;
+                return Ok(());
+            },
+            AACPacketType::Raw => match &self.config {
+                Some(config) => config,
+                None => return Ok(()),
+            },
+        };
+
+        let header = config.adts_header(packet.data.len())?;
+        self.inner.write_all(&header)?;
+        self.inner.write_all(&packet.data)?;
+        Ok(())
+    }
+
+    pub fn flush(&mut self) -> Result<(), Error> {
+        self.inner.flush()?;
+        Ok(())
+    }
+
+    pub fn into_inner(self) -> W {
+        self.inner
+    }
+}
+
+// Extracts the AAC audio track of a whole FLV file
+pub fn extract_adts<R: Read, W: Write>(input: R, out: W) -> Result<W, Error> {
+    let mut extractor = AdtsExtractor::new(out);
+    for tag in FlvReader::new(input)? {
+        extractor.write_tag(&tag?)?;
+    }
+    extractor.flush()?;
+    Ok(extractor.into_inner())
+}
@@ -0,0 +1,91 @@
+use std::io::{Read, Write};
+
+use crate::reader::FlvReader;
+use crate::tag::video::avc::{AVCDecoderConfigurationRecord, NalUnitType};
+use crate::tag::video::{AVCPacketType, AVCVideoPacket, VideoDataByFrame, VideoPacketData};
+use crate::tag::{Tag, TagData};
+use crate::Error;
+
+const START_CODE: [u8; 4] = [0, 0, 0, 1];
+
+// Writes the AVC video of an FLV file as an Annex-B H.264 elementary stream:
+// every NAL unit is prefixed with a start code instead of its length,
+// and the SPS and PPS of the last sequence header are repeated before each IDR picture.
+pub struct H264Extractor<W: Write> {
+    inner: W,
+    config: Option<AVCDecoderConfigurationRecord>,
+}
+
+impl<W: Write> H264Extractor<W> {
+    pub fn new(inner: W) -> Self {
+        H264Extractor {
+            inner,
+            config: None,
+        }
+    }
+
+    // Frames before the first sequence header cannot be decoded and are dropped
+    pub fn write_tag(&mut self, tag: &Tag) -> Result<(), Error> {
+        if let TagData::Video(video) = &tag.data {
+            if let VideoDataByFrame::VideoFramePayload(VideoPacketData::AVCVideoPacket(packet)) = &video.video_data {
+                self.write_packet(packet)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn write_packet(&mut self, packet: &AVCVideoPacket) -> Result<(), Error> {
+        let config = match packet.avc_packet_type {
+            AVCPacketType::SequenceHeader => {
+                self.config = packet.decoder_configuration_record().transpose()?;
+                return Ok(());
+            },
+            AVCPacketType::EndOfSequence => return Ok(()),
+            AVCPacketType::NALU => match &self.config {
+                Some(config) => config,
+                None => return Ok(()),
+            },
+        };
+
+        let mut out = Vec::with_capacity(packet.data.len() + 64);
+        // encoders may already repeat the parameter sets in band
+        let mut parameter_sets_written = false;
+        for nalu in packet.nal_units(config.nalu_length_size()) {
+            let nalu = nalu?;
+            match nalu.nal_unit_type {
+                NalUnitType::Sps | NalUnitType::Pps => parameter_sets_written = true,
+                NalUnitType::Idr if !parameter_sets_written => {
+                    for parameter_set in config.sequence_parameter_sets.iter().chain(&config.picture_parameter_sets) {
+                        out.extend_from_slice(&START_CODE);
+                        out.extend_from_slice(parameter_set);
+                    }
+                    parameter_sets_written = true;
+                },
+                _ => {},
+            }
+            out.extend_from_slice(&START_CODE);
+            out.extend_from_slice(nalu.data);
+        }
+        self.inner.write_all(&out)?;
+        Ok(())
+    }
+
+    pub fn flush(&mut self) -> Result<(), Error> {
+        self.inner.flush()?;
+        Ok(())
+    }
+
+    pub fn into_inner(self) -> W {
+        self.inner
+    }
+}
+
+// Extracts the AVC video track of a whole FLV file
+pub fn extract_h264<R: Read, W: Write>(input: R, out: W) -> Result<W, Error> {
+    let mut extractor = H264Extractor::new(out);
+    for tag in FlvReader::new(input)? {
+        extractor.write_tag(&tag?)?;
+    }
+    extractor.flush()?;
+    Ok(extractor.into_inner())
+}
@@ -0,0 +1,87 @@
+use std::io::{Read, Write};
+
+use crate::reader::FlvReader;
+use crate::tag::audio::mp3::{self, MpegAudioFrameHeader, MPEG_AUDIO_HEADER_SIZE};
+use crate::tag::audio::{SoundData, SoundFormat};
+use crate::tag::{Tag, TagData};
+use crate::Error;
+
+// Writes the MP3 audio of an FLV file as an `.mp3` file.
+// Frames may span tags, so the data is buffered until a whole frame is there,
+// and every frame header is checked before the frame is written.
+// Errors are reported at their offset in the MP3 stream.
+pub struct Mp3Extractor<W: Write> {
+    inner: W,
+    // the start of a frame not written yet
+    buf: Vec<u8>,
+    // offset of `buf` in the MP3 stream
+    position: usize,
+    header: Option<MpegAudioFrameHeader>,
+}
+
+impl<W: Write> Mp3Extractor<W> {
+    pub fn new(inner: W) -> Self {
+        Mp3Extractor {
+            inner,
+            buf: Vec::new(),
+            position: 0,
+            header: None,
+        }
+    }
+
+    // The header of the last frame written
+    pub fn header(&self) -> Option<&MpegAudioFrameHeader> {
+        self.header.as_ref()
+    }
+
+    pub fn write_tag(&mut self, tag: &Tag) -> Result<(), Error> {
+        if let TagData::Audio(audio) = &tag.data {
+            if let (SoundFormat::MP3 | SoundFormat::MP38KHZ, SoundData::Raw(data)) = (&audio.sound_format, &audio.sound_data) {
+                self.buf.extend_from_slice(data);
+                self.write_frames()?;
+            }
+        }
+        Ok(())
+    }
+
+    fn write_frames(&mut self) -> Result<(), Error> {
+        let mut start = 0;
+        while self.buf.len() - start >= MPEG_AUDIO_HEADER_SIZE {
+            let header = mp3::mpeg_audio_frame_header(&self.buf[start..])
+                .map_err(|e| e.shift(self.position + start))?;
+            let size = header.frame_size();
+            if self.buf.len() - start < size {
+                break;
+            }
+            self.inner.write_all(&self.buf[start..start + size])?;
+            self.header = Some(header);
+            start += size;
+        }
+        self.buf.drain(..start);
+        self.position += start;
+        Ok(())
+    }
+
+    // A last frame cut short is written as is
+    pub fn flush(&mut self) -> Result<(), Error> {
+        self.inner.write_all(&self.buf)?;
+        self.position += self.buf.len();
+        self.buf.clear();
+        self.inner.flush()?;
+        Ok(())
+    }
+
+    pub fn into_inner(self) -> W {
+        self.inner
+    }
+}
+
+// Extracts the MP3 audio track of a whole FLV file
+pub fn extract_mp3<R: Read, W: Write>(input: R, out: W) -> Result<W, Error> {
+    let mut extractor = Mp3Extractor::new(out);
+    for tag in FlvReader::new(input)? {
+        extractor.write_tag(&tag?)?;
+    }
+    extractor.flush()?;
+    Ok(extractor.into_inner())
+}
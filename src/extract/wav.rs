@@ -0,0 +1,180 @@
+use std::io::{Read, Seek, SeekFrom, Write};
+
+use crate::reader::FlvReader;
+use crate::tag::audio::adpcm::decode_adpcm;
+use crate::tag::audio::{AudioData, SoundData, SoundFormat, SoundSize};
+use crate::tag::{Tag, TagData};
+use crate::Error;
+
+pub const WAVE_FORMAT_PCM: u16 = 1;
+pub const WAVE_FORMAT_ALAW: u16 = 6;
+pub const WAVE_FORMAT_MULAW: u16 = 7;
+
+// The fmt chunk of a WAV file
+#[derive(Debug, PartialEq, Clone)]
+pub struct WavFormat {
+    pub format_tag: u16,
+    pub channels: u16,
+    pub sample_rate: u32,
+    pub bits_per_sample: u16,
+}
+
+impl WavFormat {
+    fn block_align(&self) -> u16 {
+        self.channels * self.bits_per_sample / 8
+    }
+}
+
+// Writes the uncompressed, ADPCM or G.711 audio of an FLV file as a WAV file.
+// Linear PCM and G.711 are copied, platform endian PCM being taken as little endian like the Flash Player does,
+// and ADPCM is decoded to 16 bit PCM.
+// The samples are written as they come, after a header whose sizes `finish` seeks back to fill in.
+pub struct WavExtractor<W: Write + Seek> {
+    inner: W,
+    format: Option<WavFormat>,
+    // where the header was written
+    start: u64,
+    data_size: usize,
+}
+
+impl<W: Write + Seek> WavExtractor<W> {
+    pub fn new(inner: W) -> Self {
+        WavExtractor {
+            inner,
+            format: None,
+            start: 0,
+            data_size: 0,
+        }
+    }
+
+    // The format of the first tag, which all the others must share
+    pub fn format(&self) -> Option<&WavFormat> {
+        self.format.as_ref()
+    }
+
+    pub fn write_tag(&mut self, tag: &Tag) -> Result<(), Error> {
+        if let TagData::Audio(audio) = &tag.data {
+            if let SoundData::Raw(data) = &audio.sound_data {
+                if let Some(format) = wav_format(audio) {
+                    self.write_data(audio, format, data)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn write_data(&mut self, audio: &AudioData, format: WavFormat, data: &[u8]) -> Result<(), Error> {
+        match &self.format {
+            Some(current) if *current != format => {
+                return Err(Error::Unsupported { what: "audio format change in WAV", offset: 0 });
+            },
+            Some(_) => {},
+            None => {
+                // sizes of 0 until `finish`
+                let mut header = Vec::with_capacity(58);
+                write_wav_header(&mut header, &format, 0)?;
+                self.start = self.inner.stream_position()?;
+                self.inner.write_all(&header)?;
+                self.format = Some(format);
+            },
+        }
+        match audio.sound_format {
+            SoundFormat::ADPCM => {
+                let samples = decode_adpcm(data, audio.channels() as usize)?;
+                let mut out = Vec::with_capacity(samples.len() * 2);
+                for sample in samples {
+                    out.extend_from_slice(&sample.to_le_bytes());
+                }
+                self.write_samples(&out)
+            },
+            _ => self.write_samples(data),
+        }
+    }
+
+    fn write_samples(&mut self, data: &[u8]) -> Result<(), Error> {
+        self.inner.write_all(data)?;
+        self.data_size += data.len();
+        Ok(())
+    }
+
+    // Fills in the sizes of the header, nothing is written without any audio
+    pub fn finish(mut self) -> Result<W, Error> {
+        if let Some(format) = &self.format {
+            if self.data_size % 2 == 1 {
+                // chunks are word aligned
+                self.inner.write_all(&[0])?;
+            }
+            let mut header = Vec::with_capacity(58);
+            write_wav_header(&mut header, format, self.data_size)?;
+            let end = self.inner.stream_position()?;
+            self.inner.seek(SeekFrom::Start(self.start))?;
+            self.inner.write_all(&header)?;
+            self.inner.seek(SeekFrom::Start(end))?;
+        }
+        self.inner.flush()?;
+        Ok(self.inner)
+    }
+}
+
+// None for the formats a WAV file cannot hold as they are
+fn wav_format(audio: &AudioData) -> Option<WavFormat> {
+    let (format_tag, bits_per_sample) = match audio.sound_format {
+        SoundFormat::LinearPCMPE | SoundFormat::LinearPCMLE => match audio.sound_size {
+            SoundSize::_8Bit => (WAVE_FORMAT_PCM, 8),
+            SoundSize::_16Bit => (WAVE_FORMAT_PCM, 16),
+        },
+        SoundFormat::ADPCM => (WAVE_FORMAT_PCM, 16),
+        SoundFormat::G711ALaw => (WAVE_FORMAT_ALAW, 8),
+        SoundFormat::G711MuLaw => (WAVE_FORMAT_MULAW, 8),
+        _ => return None,
+    };
+    Some(WavFormat {
+        format_tag,
+        channels: audio.channels() as u16,
+        sample_rate: audio.sample_rate(),
+        bits_per_sample,
+    })
+}
+
+// The RIFF header, fmt chunk and data chunk header.
+// Formats other than PCM have a cbSize in the fmt chunk and a fact chunk with the number of samples.
+pub fn write_wav_header(out: &mut Vec<u8>, format: &WavFormat, data_size: usize) -> Result<(), Error> {
+    let pcm = format.format_tag == WAVE_FORMAT_PCM;
+    let fmt_size = if pcm { 16 } else { 18 };
+    let fact_size = if pcm { 0 } else { 8 + 4 };
+    let riff_size = 4 + 8 + fmt_size + fact_size + 8 + data_size + data_size % 2;
+    if riff_size > u32::MAX as usize {
+        return Err(Error::TooLarge { what: "WAV data", size: data_size });
+    }
+    out.extend_from_slice(b"RIFF");
+    out.extend_from_slice(&(riff_size as u32).to_le_bytes());
+    out.extend_from_slice(b"WAVE");
+    out.extend_from_slice(b"fmt ");
+    out.extend_from_slice(&(fmt_size as u32).to_le_bytes());
+    out.extend_from_slice(&format.format_tag.to_le_bytes());
+    out.extend_from_slice(&format.channels.to_le_bytes());
+    out.extend_from_slice(&format.sample_rate.to_le_bytes());
+    out.extend_from_slice(&(format.sample_rate * format.block_align() as u32).to_le_bytes());
+    out.extend_from_slice(&format.block_align().to_le_bytes());
+    out.extend_from_slice(&format.bits_per_sample.to_le_bytes());
+    if !pcm {
+        // cbSize
+        out.extend_from_slice(&0u16.to_le_bytes());
+        out.extend_from_slice(b"fact");
+        out.extend_from_slice(&4u32.to_le_bytes());
+        let samples = data_size / format.block_align().max(1) as usize;
+        out.extend_from_slice(&(samples as u32).to_le_bytes());
+    }
+    out.extend_from_slice(b"data");
+    out.extend_from_slice(&(data_size as u32).to_le_bytes());
+    Ok(())
+}
+
+// Extracts the PCM, ADPCM or G.711 audio track of a whole FLV file
+pub fn extract_wav<R: Read, W: Write + Seek>(input: R, out: W) -> Result<W, Error> {
+    let mut extractor = WavExtractor::new(out);
+    for tag in FlvReader::new(input)? {
+        extractor.write_tag(&tag?)?;
+    }
+    extractor.finish()
+}
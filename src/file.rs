@@ -0,0 +1,118 @@
+use crate::header::{self, FLVHeader, FLV_HEADER_SIZE};
+use crate::tag::{self, Tag, UnknownTagPolicy, TAG_HEADER_SIZE};
+use crate::Error;
+
+// Every tag is followed by a uint32 holding the size of the tag it follows.
+// The body also starts with one, PreviousTagSize0, which is always 0.
+pub const PREVIOUS_TAG_SIZE: usize = 4;
+
+// A whole FLV file: the header and every tag of the body
+#[derive(Debug, PartialEq)]
+pub struct FlvFile {
+    pub header: FLVHeader,
+    pub tags: Vec<Tag>,
+}
+
+pub fn flv_file(input: &[u8]) -> Result<FlvFile, Error> {
+    let mut tags = Tags::new(input)?;
+    let mut res: Vec<Tag> = Vec::new();
+    for tag in &mut tags {
+        res.push(tag?);
+    }
+
+    Ok(FlvFile {
+        header: tags.header,
+        tags: res,
+    })
+}
+
+// Iterates the tags of an FLV file held in memory.
+// Stops at the end of input, or after the first error.
+// Error offsets are counted from the start of the file.
+pub struct Tags<'a> {
+    header: FLVHeader,
+    input: &'a [u8],
+    offset: usize,
+    unknown_tags: UnknownTagPolicy,
+    done: bool,
+}
+
+impl<'a> Tags<'a> {
+    // Parses the FLV header, then skips to `data_offset` and over PreviousTagSize0.
+    // A `data_offset` inside the header is read as the end of the header, as FlvReader does.
+    pub fn new(input: &'a [u8]) -> Result<Self, Error> {
+        let header = header::flv_header(input)?;
+        let data_offset = FLV_HEADER_SIZE + (header.data_offset as usize).saturating_sub(FLV_HEADER_SIZE);
+        let offset = data_offset.saturating_add(PREVIOUS_TAG_SIZE);
+        if input.len() < offset {
+            return Err(Error::Truncated { needed: offset, offset: 0 });
+        }
+        Ok(Tags {
+            header,
+            input,
+            offset,
+            unknown_tags: UnknownTagPolicy::default(),
+            done: false,
+        })
+    }
+
+    pub fn header(&self) -> &FLVHeader {
+        &self.header
+    }
+
+    // Unknown tags are kept by default
+    pub fn set_unknown_tag_policy(&mut self, policy: UnknownTagPolicy) {
+        self.unknown_tags = policy;
+    }
+
+    // Offset from the start of the file of the next tag
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+
+    // None for a skipped tag
+    fn next_tag(&mut self) -> Result<Option<Tag>, Error> {
+        let input = &self.input[self.offset..];
+        if input.len() < TAG_HEADER_SIZE {
+            return Err(Error::Truncated { needed: TAG_HEADER_SIZE, offset: self.offset });
+        }
+        let header = tag::tag_header(&input[..TAG_HEADER_SIZE]).map_err(|e| e.shift(self.offset))?;
+        let tag_size = TAG_HEADER_SIZE + header.data_size as usize;
+        if input.len() < tag_size {
+            return Err(Error::Truncated { needed: tag_size, offset: self.offset });
+        }
+        let tag = match self.unknown_tags {
+            UnknownTagPolicy::Error if !tag::is_known_tag_type(header.tag_type) => {
+                return Err(Error::UnknownTagType { tag_type: header.tag_type, offset: self.offset });
+            },
+            UnknownTagPolicy::Skip if !tag::is_known_tag_type(header.tag_type) => None,
+            _ => Some(tag::tag(&input[..tag_size]).map_err(|e| e.shift(self.offset))?.0),
+        };
+
+        // The last PreviousTagSize may be missing from a file cut short
+        let last = &input[tag_size..];
+        if !last.is_empty() && last.len() < PREVIOUS_TAG_SIZE {
+            return Err(Error::Truncated { needed: PREVIOUS_TAG_SIZE, offset: self.offset + tag_size });
+        }
+        self.offset += tag_size + PREVIOUS_TAG_SIZE.min(last.len());
+        Ok(tag)
+    }
+}
+
+impl Iterator for Tags<'_> {
+    type Item = Result<Tag, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while !self.done && self.offset < self.input.len() {
+            match self.next_tag() {
+                Ok(Some(tag)) => return Some(Ok(tag)),
+                Ok(None) => {},
+                Err(e) => {
+                    self.done = true;
+                    return Some(Err(e));
+                },
+            }
+        }
+        None
+    }
+}
@@ -1,5 +1,9 @@
+use crate::Error;
+
+pub const FLV_HEADER_SIZE: usize = 9;
+
 // The FLV header
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Clone)]
 pub struct FLVHeader {
     // sig: [u8;3], // Signature byte always 'FLV' (0x46 0x4c 0x56)
     pub version: u8, // File version (For example, 0x01 for FLV version 1)
@@ -16,13 +20,13 @@ pub struct FLVHeader {
     pub data_offset: u32,
 }
 
-pub fn flv_header(input: &[u8]) -> Result<FLVHeader, String> {
-    if input.len() < 9 {
-        return Err("header not enough length".to_string());
+pub fn flv_header(input: &[u8]) -> Result<FLVHeader, Error> {
+    if input.len() < FLV_HEADER_SIZE {
+        return Err(Error::Truncated { needed: FLV_HEADER_SIZE, offset: 0 });
     }
     // FLV Signature
     if input[0] == 0x46 && input[1] == 0x4c && input[2] == 0x56 {
-        return Ok(FLVHeader {
+        Ok(FLVHeader {
             version: input[3],
             audio: input[4] & 0b100 == 4,
             video: input[4] & 0b1 == 1,
@@ -34,9 +38,17 @@ pub fn flv_header(input: &[u8]) -> Result<FLVHeader, String> {
                 ]
             )
 
-        });
+        })
     } else {
-        return Err("Invalid Signature".to_string());
+        Err(Error::BadSignature { what: "FLV signature", offset: 0 })
     }
 }
 
+// Writes the header, padded with zeros up to `data_offset`
+pub fn write_flv_header(out: &mut Vec<u8>, header: &FLVHeader) {
+    out.extend_from_slice(&[0x46, 0x4c, 0x56, header.version]);
+    out.push((header.audio as u8) << 2 | header.video as u8);
+    out.extend_from_slice(&header.data_offset.to_be_bytes());
+    let padding = (header.data_offset as usize).saturating_sub(FLV_HEADER_SIZE);
+    out.resize(out.len() + padding, 0);
+}
@@ -1,5 +1,14 @@
+mod bits;
+pub mod decoder;
+mod error;
+pub mod extract;
+pub mod file;
 pub mod header;
+pub mod reader;
 pub mod tag;
+pub mod writer;
+
+pub use error::{Error, IoError};
 
 /*
    FLV File Format
@@ -14,7 +23,8 @@ static SCRIPT_DATA_NAME_TAG: &[u8] = &[2];
 #[cfg(test)]
 mod test {
     use super::*;
-    use crate::tag::audio::{SoundFormat, SoundRate, SoundSize, SoundType};
+    use std::io::Cursor;
+    use crate::tag::audio::{SoundData, SoundFormat, SoundRate, SoundSize, SoundType};
     use crate::tag::{TAG_TYPE_AUDIO, TAG_TYPE_SCRIPT, TAG_TYPE_VIDEO};
     use crate::tag::video::{CodecID, FrameType, VideoData, VideoDataByFrame, VideoPacketData};
 
@@ -60,6 +70,8 @@ mod test {
         assert_eq!(
             tag::tag_header(&ZELDA[13..24]),
             Ok(tag::TagHeader {
+                reserved: 0,
+                filter: false,
                 tag_type: TAG_TYPE_VIDEO,
                 data_size: 537,
                 timestamp: 0,
@@ -69,6 +81,8 @@ mod test {
         assert_eq!(
             tag::tag_header(&ZELDA_HQ[13..24]),
             Ok(tag::TagHeader {
+                reserved: 0,
+                filter: false,
                 tag_type: TAG_TYPE_VIDEO,
                 data_size: 2984,
                 timestamp: 0,
@@ -78,6 +92,8 @@ mod test {
         assert_eq!(
             tag::tag_header(&COMMERCIAL[13..24]),
             Ok(tag::TagHeader {
+                reserved: 0,
+                filter: false,
                 tag_type: TAG_TYPE_SCRIPT,
                 data_size: 273,
                 timestamp: 0,
@@ -103,6 +119,8 @@ mod test {
         assert_eq!(
             tag::tag_header(&ZELDA[tag_start..tag_start + 11]),
             Ok(tag::TagHeader {
+                reserved: 0,
+                filter: false,
                 tag_type: TAG_TYPE_AUDIO,
                 data_size: 642,
                 timestamp: 0,
@@ -122,7 +140,7 @@ mod test {
                 sound_rate: SoundRate::_22KHZ,
                 sound_size: SoundSize::_16Bit,
                 sound_type: SoundType::Mono,
-                sound_data: Vec::from(&ZELDA[tag_start + 12..tag_start + 11 + 642]),
+                sound_data: SoundData::Raw(Vec::from(&ZELDA[tag_start + 12..tag_start + 11 + 642])),
             })
         );
 
@@ -138,6 +156,8 @@ mod test {
         assert_eq!(
             tag::tag_header(&ZELDA_HQ[tag_start..tag_start + 11]),
             Ok(tag::TagHeader {
+                reserved: 0,
+                filter: false,
                 tag_type: TAG_TYPE_AUDIO,
                 data_size: 642,
                 timestamp: 0,
@@ -157,7 +177,7 @@ mod test {
                 sound_rate: SoundRate::_22KHZ,
                 sound_size: SoundSize::_16Bit,
                 sound_type: SoundType::Mono,
-                sound_data: Vec::from(&ZELDA_HQ[tag_start + 12..tag_start + 11 + 642]),
+                sound_data: SoundData::Raw(Vec::from(&ZELDA_HQ[tag_start + 12..tag_start + 11 + 642])),
             })
         );
     }
@@ -261,6 +281,8 @@ mod test {
             tag::tag(&ZELDA[tag_start..tag_data_start+537]),
             Ok((tag::Tag{
                 header: tag::TagHeader{
+                    reserved: 0,
+                    filter: false,
                     tag_type: TAG_TYPE_VIDEO,
                     data_size: 537,
                     timestamp: 0,
@@ -277,6 +299,8 @@ mod test {
         assert_eq!(tag::tag(&ZELDA_HQ[tag_start..tag_data_start+2984]),
             Ok((tag::Tag{
                 header: tag::TagHeader{
+                    reserved: 0,
+                    filter: false,
                     tag_type: tag::TAG_TYPE_VIDEO,
                     data_size: 2984,
                     timestamp: 0,
@@ -292,4 +316,1299 @@ mod test {
             }, &b""[..]))
         );
     }
+
+    #[test]
+    fn whole_file() {
+        let tag_start = 13;
+        let tag_data_start = tag_start + 11;
+
+        let flv = file::flv_file(ZELDA).unwrap();
+        assert_eq!(flv.header, header::flv_header(&ZELDA[..9]).unwrap());
+        assert!(flv.tags.len() > 2);
+        assert_eq!(flv.tags[0], tag::tag(&ZELDA[tag_start..tag_data_start + 537]).unwrap().0);
+        assert_eq!(flv.tags[1].header.tag_type, TAG_TYPE_AUDIO);
+        assert_eq!(flv.tags[1].header.data_size, 642);
+
+        let flv = file::flv_file(COMMERCIAL).unwrap();
+        assert_eq!(flv.tags[0].header.tag_type, TAG_TYPE_SCRIPT);
+
+        // a file cut short inside a tag yields the complete tags then an error
+        let mut tags = file::Tags::new(&ZELDA[..tag_data_start + 537 + 4 + 20]).unwrap();
+        assert!(tags.next().unwrap().is_ok());
+        assert!(tags.next().unwrap().is_err());
+        assert!(tags.next().is_none());
+
+        // a data_offset inside the header still starts the body right after it
+        let mut flv = ZELDA.to_vec();
+        flv[5..9].copy_from_slice(&[0, 0, 0, 0]);
+        let tags: Result<Vec<_>, _> = file::Tags::new(&flv).unwrap().collect();
+        assert_eq!(tags, Ok(file::flv_file(ZELDA).unwrap().tags));
+        assert_eq!(reader::FlvReader::new(&flv[..]).unwrap().collect::<Result<Vec<_>, _>>(), tags);
+    }
+
+    #[test]
+    fn stream_reader() {
+        let flv = file::flv_file(ZELDA_HQ).unwrap();
+        let reader = reader::FlvReader::new(ZELDA_HQ).unwrap();
+        assert_eq!(reader.header(), &flv.header);
+        let tags: Result<Vec<_>, _> = reader.collect();
+        assert_eq!(tags.unwrap(), flv.tags);
+
+        // the trailing PreviousTagSize is optional, a partial tag is not
+        let mut reader = reader::FlvReader::new(&ZELDA_HQ[..24 + 2984]).unwrap();
+        assert!(reader.next().unwrap().is_ok());
+        assert!(reader.next().is_none());
+        let mut reader = reader::FlvReader::new(&ZELDA_HQ[..24 + 2984 + 4 + 11 + 5]).unwrap();
+        assert!(reader.next().unwrap().is_ok());
+        assert!(reader.next().unwrap().is_err());
+        assert!(reader.next().is_none());
+    }
+
+    #[test]
+    fn push_decoder() {
+        let flv = file::flv_file(COMMERCIAL).unwrap();
+        let mut decoder = decoder::FlvDecoder::new();
+        assert_eq!(decoder.poll(), Ok(decoder::Decoded::NeedMore(9)));
+
+        let mut tags = Vec::new();
+        for chunk in COMMERCIAL.chunks(7) {
+            decoder.push(chunk);
+            loop {
+                match decoder.poll().unwrap() {
+                    decoder::Decoded::Header(header) => assert_eq!(header, flv.header),
+                    decoder::Decoded::Tag(tag) => tags.push(tag),
+                    decoder::Decoded::NeedMore(n) => {
+                        assert!(n > 0);
+                        break;
+                    },
+                }
+            }
+        }
+        assert_eq!(decoder.header(), Some(&flv.header));
+        assert_eq!(tags, flv.tags);
+        assert_eq!(decoder.buffered(), 0);
+    }
+
+    #[test]
+    fn error_offsets() {
+        let mut flv = ZELDA.to_vec();
+        flv[0] = b'G';
+        assert_eq!(file::flv_file(&flv), Err(Error::BadSignature { what: "FLV signature", offset: 0 }));
+
+        assert_eq!(
+            file::flv_file(&ZELDA[..100]),
+            Err(Error::Truncated { needed: 11 + 537, offset: 13 })
+        );
+
+        // frame type 0 in the first video tag
+        let mut flv = ZELDA.to_vec();
+        flv[24] = 0x02;
+        let err = file::flv_file(&flv).unwrap_err();
+        assert_eq!(err, Error::InvalidFrameType { frame_type: 0, offset: 24 });
+        assert_eq!(err.offset(), Some(24));
+
+        let mut reader = reader::FlvReader::new(&flv[..]).unwrap();
+        assert_eq!(reader.next(), Some(Err(Error::InvalidFrameType { frame_type: 0, offset: 24 })));
+
+        // an AMF marker that does not exist in the script tag of COMMERCIAL
+        let mut flv = COMMERCIAL.to_vec();
+        flv[24 + 13] = 0x20;
+        assert_eq!(
+            file::flv_file(&flv),
+            Err(Error::InvalidAmfMarker { marker: 0x20, offset: 24 + 13 })
+        );
+    }
+
+    #[test]
+    fn malformed_input() {
+        // every prefix of a file and of its tags fails cleanly instead of panicking
+        for end in 0..COMMERCIAL.len().min(400) {
+            let _ = file::flv_file(&COMMERCIAL[..end]);
+            let _ = tag::tag(&COMMERCIAL[13..end.max(13)]);
+            let _ = tag::amf0::amf_data(&COMMERCIAL[24..end.max(24)]);
+        }
+        assert_eq!(tag::tag_header(&ZELDA[13..20]), Err(Error::Truncated { needed: 11, offset: 0 }));
+        assert_eq!(tag::audio::audio_data(&[], 0), Err(Error::Truncated { needed: 1, offset: 0 }));
+        assert_eq!(
+            tag::amf0::amf_data_value(&[tag::amf0::AMF_DATA_TYPE_STRING, 0xff, 0xff, b'a']),
+            Err(Error::Truncated { needed: 0xffff, offset: 3 })
+        );
+
+        let nested = [tag::amf0::AMF_DATA_TYPE_ARRAY, 0, 0, 0, 1].repeat(10_000);
+        assert!(matches!(
+            tag::amf0::amf_data_value(&nested),
+            Err(Error::NestingTooDeep { .. })
+        ));
+    }
+
+    #[test]
+    fn amf0_encode() {
+        let tag_start = 24;
+        let tag_end = tag_start + 273;
+
+        let (script_data, _) = tag::amf0::amf_data(&COMMERCIAL[tag_start..tag_end]).unwrap();
+        let mut out = Vec::new();
+        tag::amf0::write_amf_data(&mut out, &script_data).unwrap();
+        assert_eq!(out, &COMMERCIAL[tag_start..tag_end]);
+
+        let values = vec![
+            tag::amf0::AMFData::Bool(true),
+            tag::amf0::AMFData::Null,
+            tag::amf0::AMFData::Undefined,
+            tag::amf0::AMFData::Reference(3),
+            tag::amf0::AMFData::Date(tag::amf0::AMFDate {
+                milliseconds: 1191523062000.0,
+                timezone: -60,
+            }),
+            tag::amf0::AMFData::LongString("long".to_string()),
+            tag::amf0::AMFData::Object(vec![tag::amf0::AMFObject {
+                name: "nested".to_string(),
+                data: tag::amf0::AMFData::Array(vec![tag::amf0::AMFData::Number(1.5)]),
+            }]),
+        ];
+        let value = tag::amf0::AMFData::Array(values);
+        let mut out = Vec::new();
+        tag::amf0::write_amf_data_value(&mut out, &value).unwrap();
+        assert_eq!(tag::amf0::amf_data_value(&out), Ok((value, &b""[..])));
+
+        // strings that do not fit a u16 length are promoted
+        let long = "a".repeat(70_000);
+        let mut out = Vec::new();
+        tag::amf0::write_amf_data_value(&mut out, &tag::amf0::AMFData::String(long.clone())).unwrap();
+        assert_eq!(out[0], tag::amf0::AMF_DATA_TYPE_LONG_STRING);
+        assert_eq!(
+            tag::amf0::amf_data_value(&out),
+            Ok((tag::amf0::AMFData::LongString(long), &b""[..]))
+        );
+    }
+
+    #[test]
+    fn typed_meta_data() {
+        let (script_data, _) = tag::amf0::amf_data(&COMMERCIAL[24..24 + 273]).unwrap();
+        let meta = tag::amf0::meta_data(&script_data).unwrap();
+        assert_eq!(meta.duration, Some(28.133));
+        assert_eq!(meta.width, Some(464.0));
+        assert_eq!(meta.height, Some(348.0));
+        assert_eq!(meta.video_codec_id, Some(4.0));
+        assert_eq!(meta.audio_data_rate, Some(56.0));
+        assert_eq!(meta.audio_delay, Some(0.0));
+        assert_eq!(meta.can_seek_to_end, Some(true));
+        assert_eq!(meta.creation_date, Some("Thu Oct 04 18:37:42 2007\n".to_string()));
+        assert_eq!(meta.stereo, None);
+        assert!(meta.extra.is_empty());
+
+        let object = tag::amf0::AMFObject {
+            name: "onMetaData".to_string(),
+            data: tag::amf0::AMFData::Object(vec![
+                tag::amf0::AMFObject {
+                    name: "keyframes".to_string(),
+                    data: tag::amf0::AMFData::Object(vec![
+                        tag::amf0::AMFObject {
+                            name: "times".to_string(),
+                            data: tag::amf0::AMFData::Array(vec![tag::amf0::AMFData::Number(0.0), tag::amf0::AMFData::Number(2.0)]),
+                        },
+                        tag::amf0::AMFObject {
+                            name: "filepositions".to_string(),
+                            data: tag::amf0::AMFData::Array(vec![tag::amf0::AMFData::Number(13.0), tag::amf0::AMFData::Number(4096.0)]),
+                        },
+                    ]),
+                },
+                tag::amf0::AMFObject {
+                    name: "encoder".to_string(),
+                    data: tag::amf0::AMFData::String("Lavf60.3.100".to_string()),
+                },
+                tag::amf0::AMFObject {
+                    name: "width".to_string(),
+                    data: tag::amf0::AMFData::String("wide".to_string()),
+                },
+                tag::amf0::AMFObject {
+                    name: "custom".to_string(),
+                    data: tag::amf0::AMFData::Bool(false),
+                },
+            ]),
+        };
+        let meta = tag::amf0::meta_data(&object).unwrap();
+        assert_eq!(meta.keyframes, Some(tag::amf0::KeyFrames {
+            times: vec![0.0, 2.0],
+            file_positions: vec![13.0, 4096.0],
+        }));
+        assert_eq!(meta.encoder, Some("Lavf60.3.100".to_string()));
+        assert_eq!(meta.width, None);
+        assert_eq!(meta.extra.len(), 2);
+        assert_eq!(meta.extra["custom"], tag::amf0::AMFData::Bool(false));
+
+        assert_eq!(tag::amf0::meta_data(&tag::amf0::AMFObject {
+            name: "onCuePoint".to_string(),
+            data: tag::amf0::AMFData::Null,
+        }), None);
+    }
+
+    #[test]
+    fn write_round_trip() {
+        for asset in [ZELDA, ZELDA_HQ, COMMERCIAL] {
+            let reader = reader::FlvReader::new(asset).unwrap();
+            let mut writer = writer::FlvWriter::new(Vec::new(), reader.header()).unwrap();
+            for tag in reader {
+                writer.write_tag(&tag.unwrap()).unwrap();
+            }
+            assert!(writer.into_inner() == asset);
+        }
+
+        let mut writer = writer::FlvWriter::new(Vec::new(), &header::FLVHeader {
+            version: 1,
+            audio: true,
+            video: false,
+            data_offset: 9,
+        }).unwrap();
+        writer.write_raw_tag(TAG_TYPE_AUDIO, 0x0102_0304, &[0x2e, 1, 2, 3]).unwrap();
+        let out = writer.into_inner();
+        assert_eq!(&out[..13], b"FLV\x01\x04\x00\x00\x00\x09\x00\x00\x00\x00");
+        assert_eq!(&out[13..24], &[TAG_TYPE_AUDIO, 0, 0, 4, 2, 3, 4, 1, 0, 0, 0]);
+        assert_eq!(&out[28..], &[0, 0, 0, 15]);
+
+        let flv = file::flv_file(&out).unwrap();
+        assert_eq!(flv.tags[0].header.timestamp, 0x0102_0304);
+
+        // reserved sound formats and video command frames come back byte for byte
+        let mut writer = writer::FlvWriter::new(Vec::new(), &flv.header).unwrap();
+        writer.write_raw_tag(TAG_TYPE_AUDIO, 0, &[0xd2, 1, 2]).unwrap();
+        writer.write_raw_tag(TAG_TYPE_VIDEO, 0, &[0x52, 0, 7, 8]).unwrap();
+        let mut out = writer.into_inner();
+        // the reserved bits of the first TagHeader
+        out[13] |= 0xc0;
+        let flv = file::flv_file(&out).unwrap();
+        let mut writer = writer::FlvWriter::new(Vec::new(), &flv.header).unwrap();
+        for tag in &flv.tags {
+            writer.write_tag(tag).unwrap();
+        }
+        assert_eq!(writer.into_inner(), out);
+        assert_eq!(flv.tags[0].header.reserved, 0b11);
+
+        // the tag type follows the data, not the header
+        let mut tag = flv.tags.into_iter().next().unwrap();
+        tag.header.tag_type = TAG_TYPE_VIDEO;
+        let mut out = Vec::new();
+        tag::write_tag(&mut out, &tag).unwrap();
+        assert_eq!(out[0] & 0b1_1111, TAG_TYPE_AUDIO);
+        tag.header.tag_type = TAG_TYPE_AUDIO;
+        assert_eq!(tag::tag(&out), Ok((tag, &b""[..])));
+    }
+
+    #[test]
+    fn avc_decoder_configuration_record() {
+        use crate::tag::video::avc::{AVCDecoderConfigurationRecord, AVCHighProfileExtension};
+
+        let sequence_header = [
+            0, 0, 0, 0, // AVCPacketType, CompositionTime
+            1, 100, 0, 31, 0xff, 0xe1, // version, profile, compatibility, level, length size, 1 SPS
+            0, 4, 0x67, 100, 0, 31,
+            1, // 1 PPS
+            0, 3, 0x68, 0xeb, 0xe3,
+            0xfd, 0xf8, 0xf8, 0, // 4:2:0, 8 bits, no SPS ext
+        ];
+        let packet = tag::video::avc_video_packet(&sequence_header, sequence_header.len()).unwrap();
+        let record = packet.decoder_configuration_record().unwrap().unwrap();
+        assert_eq!(record, AVCDecoderConfigurationRecord {
+            configuration_version: 1,
+            profile_indication: 100,
+            profile_compatibility: 0,
+            level_indication: 31,
+            length_size_minus_one: 3,
+            sequence_parameter_sets: vec![vec![0x67, 100, 0, 31]],
+            picture_parameter_sets: vec![vec![0x68, 0xeb, 0xe3]],
+            high_profile: Some(AVCHighProfileExtension {
+                chroma_format: 1,
+                bit_depth_luma_minus8: 0,
+                bit_depth_chroma_minus8: 0,
+                sequence_parameter_set_ext: vec![],
+            }),
+        });
+        assert_eq!(record.nalu_length_size(), 4);
+
+        // the high profile extension is optional
+        let record = tag::video::avc::avc_decoder_configuration_record(&sequence_header[4..22]).unwrap();
+        assert_eq!(record.high_profile, None);
+
+        assert_eq!(
+            tag::video::avc::avc_decoder_configuration_record(&sequence_header[4..20]),
+            Err(Error::Truncated { needed: 3, offset: 15 })
+        );
+    }
+
+    #[test]
+    fn sequence_parameter_set() {
+        use crate::tag::video::sps::{sequence_parameter_set, TimingInfo};
+
+        // Baseline 1280x720, square pixels, 30 fps
+        let baseline = [0x67, 0x42, 0x00, 0x1e, 0xec, 0xa0, 0x28, 0x02, 0xdd, 0x80, 0xb5, 0x01, 0x01, 0x01, 0x40, 0x00, 0x00, 0x03, 0x00, 0x40, 0x00, 0x00, 0x0f, 0x21];
+        let record = tag::video::avc::avc_decoder_configuration_record(
+            &[&[1, 0x42, 0, 0x1e, 0xff, 0xe1, 0, baseline.len() as u8][..], &baseline, &[0]].concat(),
+        ).unwrap();
+        let sps = record.sequence_parameter_set().unwrap().unwrap();
+        assert_eq!(sps.profile_idc, 66);
+        assert_eq!(sps.level_idc, 30);
+        assert_eq!((sps.width(), sps.height()), (1280, 720));
+        assert_eq!(sps.sample_aspect_ratio, Some((1, 1)));
+        assert_eq!(sps.timing, Some(TimingInfo {
+            num_units_in_tick: 1,
+            time_scale: 60,
+            fixed_frame_rate: true,
+        }));
+        assert_eq!(sps.frame_rate(), Some(30.0));
+
+        // High 1920x1088 cropped to 1080, with scaling lists, extended SAR and 29.97 fps
+        let high = [0x67, 0x64, 0x00, 0x28, 0xad, 0x95, 0x22, 0xa4, 0x54, 0x8a, 0x91, 0x52, 0x28, 0x25, 0x48, 0xa9, 0x15, 0x22, 0xa4, 0x54, 0x8a, 0x91, 0x52, 0x2a, 0x45, 0x48, 0xa9, 0x15, 0x22, 0xa4, 0x54, 0x8a, 0x91, 0x52, 0x2a, 0x45, 0x48, 0xa9, 0x15, 0x22, 0xa4, 0x54, 0x8a, 0xd9, 0x40, 0x78, 0x02, 0x27, 0xe5, 0xff, 0xc0, 0x01, 0x00, 0x00, 0xda, 0x80, 0x80, 0x80, 0xa0, 0x00, 0x00, 0x7d, 0x20, 0x00, 0x1d, 0x4c, 0x10, 0x80];
+        let sps = sequence_parameter_set(&high).unwrap();
+        assert_eq!(sps.profile_idc, 100);
+        assert_eq!(sps.chroma_format_idc, 1);
+        assert_eq!(sps.bit_depth_luma, 8);
+        assert_eq!((sps.coded_width, sps.coded_height), (1920, 1088));
+        assert_eq!((sps.width(), sps.height()), (1920, 1080));
+        assert_eq!(sps.sample_aspect_ratio, Some((4, 3)));
+        assert!((sps.frame_rate().unwrap() - 29.97).abs() < 0.001);
+
+        // Main 720x576 interlaced, pic_order_cnt_type 1, no VUI
+        let interlaced = [0x67, 0x4d, 0x00, 0x1e, 0xd0, 0xe2, 0x9a, 0x65, 0x02, 0xd0, 0x93, 0xfa];
+        let sps = sequence_parameter_set(&interlaced).unwrap();
+        assert!(!sps.frame_mbs_only);
+        assert_eq!((sps.width(), sps.height()), (720, 576));
+        assert_eq!(sps.sample_aspect_ratio, None);
+        assert_eq!(sps.frame_rate(), None);
+
+        assert!(matches!(sequence_parameter_set(&interlaced[..6]), Err(Error::Truncated { .. })));
+    }
+
+    #[test]
+    fn avc_video_tags() {
+        use crate::tag::video::{AVCPacketType, AVCVideoPacket};
+
+        // an inter frame at 100ms, displayed 50ms earlier
+        let mut flv = writer::FlvWriter::new(Vec::new(), &header::FLVHeader {
+            version: 1,
+            audio: false,
+            video: true,
+            data_offset: 9,
+        }).unwrap();
+        flv.write_raw_tag(TAG_TYPE_VIDEO, 100, &[0x27, 1, 0xff, 0xff, 0xce, 0, 0, 0, 2, 0x01, 0x9a]).unwrap();
+        let flv = flv.into_inner();
+
+        let (tag, _) = tag::tag(&flv[13..]).unwrap();
+        assert_eq!(tag.data, tag::TagData::Video(VideoData {
+            frame_type: FrameType::Inter,
+            codec_id: CodecID::AVC,
+            video_data: VideoDataByFrame::VideoFramePayload(VideoPacketData::AVCVideoPacket(AVCVideoPacket {
+                avc_packet_type: AVCPacketType::NALU,
+                composition_time: -50,
+                data: vec![0, 0, 0, 2, 0x01, 0x9a],
+            })),
+        }));
+        assert_eq!(tag.dts(), 100);
+        assert_eq!(tag.pts(), 50);
+
+        let mut out = Vec::new();
+        tag::write_tag(&mut out, &tag).unwrap();
+        assert_eq!(out, &flv[13..flv.len() - 4]);
+
+        assert_eq!(
+            tag::video::video_data(&[0x17, 3, 0, 0, 0], 5),
+            Err(Error::InvalidAvcPacketType { packet_type: 3, offset: 1 })
+        );
+    }
+
+    #[test]
+    fn enhanced_video_tags() {
+        use crate::tag::video::hevc::{HEVCNalUnitArray, HEVC_NAL_VPS};
+        use crate::tag::video::{ExVideoCommand, ExVideoPacket, VideoPacketType};
+
+        fn ex_packet(data: &tag::video::VideoData) -> &ExVideoPacket {
+            match &data.video_data {
+                VideoDataByFrame::VideoFramePayload(VideoPacketData::ExVideoPacket(packet)) => packet,
+                other => panic!("expected an Enhanced FLV packet, got {:?}", other),
+            }
+        }
+
+        // HEVC Main, level 3.1, with a VPS
+        let sequence_start = [
+            &[0x90][..], b"hvc1",
+            &[1, 0x01, 0x60, 0, 0, 0, 0x90, 0, 0, 0, 0, 0, 93, 0xf0, 0, 0xfc, 0xfd, 0xf8, 0xf8, 0, 0, 0x0f, 1],
+            &[0xa0, 0, 1, 0, 3, 0x40, 0x01, 0x0c],
+        ].concat();
+        let video = tag::video::video_data(&sequence_start, sequence_start.len()).unwrap();
+        assert_eq!((&video.frame_type, &video.codec_id), (&FrameType::Key, &CodecID::HEVC));
+        let packet = ex_packet(&video);
+        assert_eq!(packet.packet_type, VideoPacketType::SequenceStart);
+        let record = packet.hevc_decoder_configuration_record().unwrap().unwrap();
+        assert_eq!(record.general_profile_idc, 1);
+        assert_eq!(record.general_profile_compatibility_flags, 0x6000_0000);
+        assert_eq!(record.general_constraint_indicator_flags, 0x9000_0000_0000);
+        assert_eq!(record.general_level_idc, 93);
+        assert_eq!(record.chroma_format, 1);
+        assert_eq!(record.nalu_length_size(), 4);
+        assert_eq!(record.arrays, vec![HEVCNalUnitArray {
+            array_completeness: true,
+            nal_unit_type: HEVC_NAL_VPS,
+            nal_units: vec![vec![0x40, 0x01, 0x0c]],
+        }]);
+        assert_eq!(record.nal_units(HEVC_NAL_VPS).count(), 1);
+        let mut out = Vec::new();
+        tag::video::write_video_data(&mut out, &video).unwrap();
+        assert_eq!(out, sequence_start);
+
+        // an HEVC inter frame at 100ms, displayed 33ms earlier
+        let mut flv = writer::FlvWriter::new(Vec::new(), &header::FLVHeader {
+            version: 1,
+            audio: false,
+            video: true,
+            data_offset: 9,
+        }).unwrap();
+        flv.write_raw_tag(TAG_TYPE_VIDEO, 100, &[&[0xa1][..], b"hvc1", &[0xff, 0xff, 0xdf, 0, 0, 0, 2, 0x02, 0x01]].concat()).unwrap();
+        let flv = flv.into_inner();
+        let (tag, _) = tag::tag(&flv[13..]).unwrap();
+        assert_eq!(tag.data, tag::TagData::Video(VideoData {
+            frame_type: FrameType::Inter,
+            codec_id: CodecID::HEVC,
+            video_data: VideoDataByFrame::VideoFramePayload(VideoPacketData::ExVideoPacket(ExVideoPacket {
+                packet_type: VideoPacketType::CodedFrames,
+                composition_time: -33,
+                data: vec![0, 0, 0, 2, 0x02, 0x01],
+            })),
+        }));
+        assert_eq!(tag.pts(), 67);
+        let mut out = Vec::new();
+        tag::write_tag(&mut out, &tag).unwrap();
+        assert_eq!(out, &flv[13..flv.len() - 4]);
+
+        // AV1 main profile, 4:2:0, then frames without composition time
+        let video = tag::video::video_data(&[&[0x90][..], b"av01", &[0x81, 0x08, 0x0c, 0x00, 0x0a, 0x0b]].concat(), 11).unwrap();
+        let record = ex_packet(&video).av1_codec_configuration_record().unwrap().unwrap();
+        assert_eq!((record.seq_profile, record.seq_level_idx_0), (0, 8));
+        assert!(record.chroma_subsampling_x && record.chroma_subsampling_y);
+        assert_eq!(record.initial_presentation_delay, None);
+        assert_eq!(record.config_obus, vec![0x0a, 0x0b]);
+        let video = tag::video::video_data(&[&[0xa3][..], b"av01", &[0x12, 0x00]].concat(), 7).unwrap();
+        assert_eq!(ex_packet(&video).packet_type, VideoPacketType::CodedFramesX);
+        assert_eq!(ex_packet(&video).data, vec![0x12, 0x00]);
+
+        // VP9 profile 0, 8 bits
+        let video = tag::video::video_data(&[&[0x90][..], b"vp09", &[1, 0, 0, 0, 0, 31, 0x82, 1, 1, 1, 0, 0]].concat(), 17).unwrap();
+        let record = ex_packet(&video).vp_codec_configuration_record().unwrap().unwrap();
+        assert_eq!((record.profile, record.level, record.bit_depth, record.chroma_subsampling), (0, 31, 8, 1));
+
+        // HDR metadata
+        let metadata = [&[0x94][..], b"hvc1", &[2, 0, 9], b"colorInfo", &[3, 0, 0, 9]].concat();
+        let video = tag::video::video_data(&metadata, metadata.len()).unwrap();
+        let (name, value) = ex_packet(&video).metadata().unwrap().unwrap();
+        assert_eq!(name, "colorInfo");
+        assert!(matches!(value, tag::amf0::AMFData::Object(_)));
+
+        // a command frame has a VideoCommand instead of a FourCC
+        let video = tag::video::video_data(&[0xd0, 0x01], 2).unwrap();
+        assert_eq!(video, VideoData {
+            frame_type: FrameType::Video,
+            codec_id: CodecID::None,
+            video_data: VideoDataByFrame::ExCommand(ExVideoCommand {
+                packet_type: VideoPacketType::SequenceStart,
+                command: 1,
+                data: vec![],
+            }),
+        });
+        let mut out = Vec::new();
+        tag::video::write_video_data(&mut out, &video).unwrap();
+        assert_eq!(out, [0xd0, 0x01]);
+        assert_eq!(tag::video::video_data(&[0xd0], 1), Err(Error::Truncated { needed: 1, offset: 1 }));
+
+        assert_eq!(
+            tag::video::video_data(&[&[0x99][..], b"hvc1"].concat(), 5),
+            Err(Error::InvalidVideoPacketType { packet_type: 9, offset: 0 })
+        );
+        assert_eq!(
+            tag::video::video_data(&[&[0x96][..], b"hvc1"].concat(), 5),
+            Err(Error::Unsupported { what: "multitrack video", offset: 0 })
+        );
+
+        // an unknown FourCC is kept with its body, without a composition time
+        let unknown = [&[0x91][..], b"avc2", &[0, 0, 1]].concat();
+        let video = tag::video::video_data(&unknown, unknown.len()).unwrap();
+        assert_eq!(video.codec_id, CodecID::Unknown(*b"avc2"));
+        assert_eq!(ex_packet(&video).composition_time, 0);
+        assert_eq!(ex_packet(&video).data, vec![0, 0, 1]);
+        let mut out = Vec::new();
+        tag::video::write_video_data(&mut out, &video).unwrap();
+        assert_eq!(out, unknown);
+
+        assert_eq!(
+            tag::video::video_data(&[&[0x91][..], b"hvc1", &[0, 0]].concat(), 7),
+            Err(Error::Truncated { needed: 3, offset: 5 })
+        );
+    }
+
+    #[test]
+    fn encrypted_tags() {
+        use crate::tag::encryption::{EncryptedData, EncryptionTagHeader, FilterParams, MediaHeader};
+        use crate::tag::video::{AVCPacketType, VideoTagHeader};
+
+        fn filtered_tag(tag_type: u8, timestamp: u32, body: &[u8]) -> Vec<u8> {
+            let mut out = Vec::new();
+            tag::write_tag_header(&mut out, &tag::TagHeader {
+                reserved: 0,
+                filter: true,
+                tag_type,
+                data_size: body.len() as u32,
+                timestamp,
+                stream_id: 0,
+            }).unwrap();
+            out.extend_from_slice(body);
+            out
+        }
+
+        // an AVC key frame at 100ms, displayed 33ms later
+        let iv: Vec<u8> = (0..16).collect();
+        let input = filtered_tag(TAG_TYPE_VIDEO, 100, &[
+            &[0x17, 1, 0, 0, 33][..], &[1], &[0, 10], b"Encryption", &[0, 0, 16], &iv, &[0xde, 0xad],
+        ].concat());
+        assert_eq!(input[0], 0x29);
+        let header = tag::tag_header(&input).unwrap();
+        assert!(header.filter);
+        assert_eq!(header.tag_type, TAG_TYPE_VIDEO);
+        let (tag, _) = tag::tag(&input).unwrap();
+        assert_eq!(tag.data, tag::TagData::Encrypted(EncryptedData {
+            media_header: MediaHeader::Video(VideoTagHeader {
+                frame_type: FrameType::Key,
+                codec_id: CodecID::AVC,
+                avc_packet_type: Some(AVCPacketType::NALU),
+                composition_time: 33,
+            }),
+            encryption_header: EncryptionTagHeader {
+                num_filters: 1,
+                filter_name: String::from("Encryption"),
+            },
+            filter_params: FilterParams::Encryption { iv: iv.clone().try_into().unwrap() },
+            encrypted_body: vec![0xde, 0xad],
+        }));
+        assert_eq!(tag.pts(), 133);
+        let mut out = Vec::new();
+        tag::write_tag(&mut out, &tag).unwrap();
+        assert_eq!(out, input);
+
+        // selective encryption of AAC, this frame is in the clear
+        let input = filtered_tag(TAG_TYPE_AUDIO, 0, &[&[0xaf, 1][..], &[1], &[0, 2], b"SE", &[0, 0, 1], &[0], &[0x21]].concat());
+        let (tag, _) = tag::tag(&input).unwrap();
+        match &tag.data {
+            tag::TagData::Encrypted(data) => {
+                assert!(matches!(&data.media_header, MediaHeader::Audio(header) if header.sound_format == SoundFormat::AAC));
+                assert_eq!(data.filter_params, FilterParams::SelectiveEncryption { encrypted_au: false, iv: None });
+                assert_eq!(data.encrypted_body, vec![0x21]);
+            },
+            other => panic!("expected an encrypted tag, got {:?}", other),
+        }
+        let mut out = Vec::new();
+        tag::write_tag(&mut out, &tag).unwrap();
+        assert_eq!(out, input);
+
+        assert_eq!(
+            tag::tag(&filtered_tag(TAG_TYPE_VIDEO, 0, &[&[0x17, 1, 0, 0, 0][..], &[1], &[0, 3], b"XOR", &[0, 0, 0]].concat())).map(|_| ()),
+            Err(Error::Unsupported { what: "tag filter", offset: 11 + 6 })
+        );
+        assert_eq!(
+            tag::tag(&filtered_tag(TAG_TYPE_AUDIO, 0, &[&[0x2f][..], &[1], &[0, 10], b"Encryption", &[0, 0, 16], &[0; 8]].concat())).map(|_| ()),
+            Err(Error::Truncated { needed: 16, offset: 11 + 17 })
+        );
+    }
+
+    #[test]
+    fn audio_specific_config() {
+        use crate::tag::audio::aac::{audio_specific_config, AudioSpecificConfig};
+        use crate::tag::audio::{AACAudioData, AACPacketType};
+
+        // AAC-LC, 44.1 kHz stereo
+        let audio = tag::audio::audio_data(&[0xaf, 0, 0x12, 0x10], 4).unwrap();
+        assert_eq!(audio.sound_format, SoundFormat::AAC);
+        let packet = match &audio.sound_data {
+            SoundData::AAC(packet) => packet,
+            other => panic!("expected an AAC packet, got {:?}", other),
+        };
+        assert_eq!(packet.aac_packet_type, AACPacketType::SequenceHeader);
+        let config = packet.audio_specific_config().unwrap().unwrap();
+        assert_eq!(config, AudioSpecificConfig {
+            audio_object_type: 2,
+            sampling_frequency_index: 4,
+            sampling_frequency: 44100,
+            channel_configuration: 2,
+            frame_length_flag: false,
+            extension_audio_object_type: None,
+            extension_sampling_frequency: None,
+            sbr_present: false,
+            ps_present: false,
+        });
+        assert_eq!(config.channels(), Some(2));
+        assert_eq!(config.output_sampling_frequency(), 44100);
+        let mut out = Vec::new();
+        tag::audio::write_audio_data(&mut out, &audio);
+        assert_eq!(out, [0xaf, 0, 0x12, 0x10]);
+
+        let frame = AACAudioData { aac_packet_type: AACPacketType::Raw, data: vec![0x21] };
+        assert_eq!(frame.audio_specific_config(), None);
+
+        // HE-AAC with implicit signalling: 22.05 kHz core, 44.1 kHz output
+        let config = audio_specific_config(&[0x2b, 0x92, 0x08, 0x00]).unwrap();
+        assert_eq!(config.audio_object_type, 2);
+        assert_eq!(config.extension_audio_object_type, Some(5));
+        assert!(config.sbr_present && !config.ps_present);
+        assert_eq!(config.sampling_frequency, 22050);
+        assert_eq!(config.output_sampling_frequency(), 44100);
+
+        // HE-AAC v2 with explicit signalling: mono core, parametric stereo output
+        let config = audio_specific_config(&[0x13, 0x88, 0x56, 0xe5, 0xa5, 0x48, 0x80]).unwrap();
+        assert_eq!(config.audio_object_type, 2);
+        assert!(config.sbr_present && config.ps_present);
+        assert_eq!(config.channel_configuration, 1);
+        assert_eq!(config.channels(), Some(2));
+        assert_eq!(config.output_sampling_frequency(), 44100);
+
+        // escaped object type (USAC) and explicit frequency
+        let config = audio_specific_config(&[0xf9, 0x5e, 0x01, 0x86, 0xa0, 0x40]).unwrap();
+        assert_eq!(config.audio_object_type, 42);
+        assert_eq!(config.sampling_frequency_index, 15);
+        assert_eq!(config.sampling_frequency, 50000);
+        assert_eq!(config.channels(), Some(2));
+
+        assert_eq!(
+            audio_specific_config(&[0x16, 0x90]),
+            Err(Error::Unsupported { what: "reserved AAC sampling frequency index", offset: 0 })
+        );
+        assert!(matches!(audio_specific_config(&[0x12]), Err(Error::Truncated { .. })));
+        assert_eq!(
+            tag::audio::audio_data(&[0xaf, 2], 2),
+            Err(Error::InvalidAacPacketType { packet_type: 2, offset: 1 })
+        );
+    }
+
+    #[test]
+    fn enhanced_audio_tags() {
+        use crate::tag::audio::opus::OpusChannelMapping;
+        use crate::tag::audio::{AudioChannelOrder, AudioCodec, AudioPacketType, ExAudioPacket, MultichannelConfig};
+
+        fn ex_packet(data: &tag::audio::AudioData) -> &ExAudioPacket {
+            match &data.sound_data {
+                SoundData::Ex(packet) => packet,
+                other => panic!("expected an Enhanced FLV packet, got {:?}", other),
+            }
+        }
+
+        // Opus 5.1, Vorbis channel order
+        let sequence_start = [
+            &[0x90][..], b"Opus",
+            b"OpusHead", &[1, 6, 0x38, 0x01, 0x80, 0xbb, 0, 0, 0, 0, 1, 4, 2, 0, 4, 1, 2, 3, 5],
+        ].concat();
+        let audio = tag::audio::audio_data(&sequence_start, sequence_start.len()).unwrap();
+        assert_eq!(audio.sound_format, SoundFormat::ExHeader);
+        let packet = ex_packet(&audio);
+        assert_eq!((&packet.packet_type, &packet.codec), (&AudioPacketType::SequenceStart, &AudioCodec::Opus));
+        let head = packet.opus_head().unwrap().unwrap();
+        assert_eq!(head.channel_count, 6);
+        assert_eq!(head.pre_skip, 312);
+        assert_eq!(head.input_sample_rate, 48000);
+        assert_eq!(head.channel_mapping, Some(OpusChannelMapping {
+            stream_count: 4,
+            coupled_count: 2,
+            mapping: vec![0, 4, 1, 2, 3, 5],
+        }));
+        let err = tag::audio::opus::opus_head(&[0; 19]).unwrap_err();
+        assert_eq!(err.to_string(), "bad 'OpusHead' magic at offset 0");
+        let mut out = Vec::new();
+        tag::audio::write_audio_data(&mut out, &audio);
+        assert_eq!(out, sequence_start);
+
+        let config = [&[0x94][..], b"Opus", &[1, 6, 0, 0, 0, 0x3f]].concat();
+        let audio = tag::audio::audio_data(&config, config.len()).unwrap();
+        assert_eq!(ex_packet(&audio).multichannel_config().unwrap(), Ok(MultichannelConfig {
+            channel_count: 6,
+            channel_order: AudioChannelOrder::Native(0x3f),
+        }));
+
+        // FLAC 44.1 kHz stereo 16 bits, with the file marker
+        let sequence_start = [
+            &[0x90][..], b"fLaC",
+            b"fLaC", &[0x80, 0, 0, 34],
+            &[0x10, 0, 0x10, 0, 0, 0, 0x0e, 0, 0x2a, 0x5e, 0x0a, 0xc4, 0x42, 0xf0, 0, 0x04, 0x1e, 0xb0],
+            &[0; 16],
+        ].concat();
+        let audio = tag::audio::audio_data(&sequence_start, sequence_start.len()).unwrap();
+        let stream_info = ex_packet(&audio).flac_stream_info().unwrap().unwrap();
+        assert_eq!((stream_info.min_block_size, stream_info.max_block_size), (4096, 4096));
+        assert_eq!((stream_info.sample_rate, stream_info.channels, stream_info.bits_per_sample), (44100, 2, 16));
+        assert_eq!(stream_info.total_samples, 0x41eb0);
+
+        // AAC through the FourCC, then its frames
+        let audio = tag::audio::audio_data(&[&[0x90][..], b"mp4a", &[0x12, 0x10]].concat(), 7).unwrap();
+        assert_eq!(ex_packet(&audio).audio_specific_config().unwrap().unwrap().sampling_frequency, 44100);
+        let audio = tag::audio::audio_data(&[&[0x91][..], b"mp4a", &[0x21]].concat(), 6).unwrap();
+        assert_eq!(ex_packet(&audio), &ExAudioPacket {
+            packet_type: AudioPacketType::CodedFrames,
+            codec: AudioCodec::AAC,
+            data: vec![0x21],
+        });
+
+        assert_eq!(
+            tag::audio::audio_data(&[&[0x93][..], b"Opus"].concat(), 5),
+            Err(Error::InvalidAudioPacketType { packet_type: 3, offset: 0 })
+        );
+        let unknown = [&[0x91][..], b"vorb", &[1, 2]].concat();
+        let audio = tag::audio::audio_data(&unknown, unknown.len()).unwrap();
+        assert_eq!(ex_packet(&audio).codec, AudioCodec::Unknown(*b"vorb"));
+        assert_eq!(ex_packet(&audio).data, vec![1, 2]);
+        let mut out = Vec::new();
+        tag::audio::write_audio_data(&mut out, &audio);
+        assert_eq!(out, unknown);
+        assert_eq!(
+            tag::audio::audio_data(&[0x91, b'O'], 2),
+            Err(Error::Truncated { needed: 4, offset: 1 })
+        );
+    }
+
+    #[test]
+    fn nal_units() {
+        use crate::tag::video::avc::{nal_units, NalUnit, NalUnitType};
+        use crate::tag::video::{AVCPacketType, AVCVideoPacket};
+
+        let data = [
+            0, 0, 0, 2, 0x09, 0xf0, // AUD
+            0, 0, 0, 3, 0x06, 0x05, 0x80, // SEI
+            0, 0, 0, 0, // empty, skipped
+            0, 0, 0, 3, 0x65, 0x88, 0x84, // IDR
+        ];
+        let units: Vec<_> = nal_units(&data, 4).collect::<Result<_, _>>().unwrap();
+        assert_eq!(units, vec![
+            NalUnit { nal_ref_idc: 0, nal_unit_type: NalUnitType::Aud, data: &data[4..6] },
+            NalUnit { nal_ref_idc: 0, nal_unit_type: NalUnitType::Sei, data: &data[10..13] },
+            NalUnit { nal_ref_idc: 3, nal_unit_type: NalUnitType::Idr, data: &data[21..24] },
+        ]);
+
+        let packet = AVCVideoPacket {
+            avc_packet_type: AVCPacketType::NALU,
+            composition_time: 0,
+            data: data.to_vec(),
+        };
+        assert_eq!(packet.is_idr(4), Ok(true));
+
+        // two byte lengths, the second one runs past the end
+        let data = [0, 1, 0x41, 0, 9, 0x41, 0x9a];
+        let mut units = nal_units(&data, 2);
+        assert_eq!(units.next().unwrap().unwrap().nal_unit_type, NalUnitType::NonIdrSlice);
+        assert_eq!(units.next(), Some(Err(Error::Truncated { needed: 9, offset: 5 })));
+        assert_eq!(units.next(), None);
+    }
+
+    #[test]
+    fn extract_h264() {
+        let mut flv = writer::FlvWriter::new(Vec::new(), &header::FLVHeader {
+            version: 1,
+            audio: false,
+            video: true,
+            data_offset: 9,
+        }).unwrap();
+        // dropped, no sequence header yet
+        flv.write_raw_tag(TAG_TYPE_VIDEO, 0, &[0x27, 1, 0, 0, 0, 0, 0, 0, 2, 0x41, 0x9a]).unwrap();
+        flv.write_raw_tag(TAG_TYPE_VIDEO, 0, &[
+            0x17, 0, 0, 0, 0,
+            1, 0x42, 0, 0x1e, 0xff, 0xe1, 0, 3, 0x67, 0x42, 0x1e, 1, 0, 2, 0x68, 0xce,
+        ]).unwrap();
+        flv.write_raw_tag(TAG_TYPE_AUDIO, 0, &[0x2e, 0xff]).unwrap();
+        flv.write_raw_tag(TAG_TYPE_VIDEO, 0, &[
+            0x17, 1, 0, 0, 0,
+            0, 0, 0, 2, 0x09, 0xf0,
+            0, 0, 0, 3, 0x65, 0x88, 0x84,
+        ]).unwrap();
+        flv.write_raw_tag(TAG_TYPE_VIDEO, 33, &[0x27, 1, 0, 0, 0, 0, 0, 0, 2, 0x41, 0x9a]).unwrap();
+        let flv = flv.into_inner();
+
+        let h264 = extract::h264::extract_h264(&flv[..], Vec::new()).unwrap();
+        assert_eq!(h264, [
+            0, 0, 0, 1, 0x09, 0xf0,
+            0, 0, 0, 1, 0x67, 0x42, 0x1e,
+            0, 0, 0, 1, 0x68, 0xce,
+            0, 0, 0, 1, 0x65, 0x88, 0x84,
+            0, 0, 0, 1, 0x41, 0x9a,
+        ]);
+
+        // no AVC video in the test assets
+        assert_eq!(extract::h264::extract_h264(ZELDA, Vec::new()), Ok(vec![]));
+    }
+
+    #[test]
+    fn extract_adts() {
+        let mut flv = writer::FlvWriter::new(Vec::new(), &header::FLVHeader {
+            version: 1,
+            audio: true,
+            video: false,
+            data_offset: 9,
+        }).unwrap();
+        // dropped, no sequence header yet
+        flv.write_raw_tag(TAG_TYPE_AUDIO, 0, &[0xaf, 1, 0x21, 0x10]).unwrap();
+        // AAC-LC, 44.1 kHz stereo
+        flv.write_raw_tag(TAG_TYPE_AUDIO, 0, &[0xaf, 0, 0x12, 0x10]).unwrap();
+        flv.write_raw_tag(TAG_TYPE_AUDIO, 0, &[0xaf, 1, 0x21, 0x00]).unwrap();
+        // AAC-LC, 22.05 kHz mono
+        flv.write_raw_tag(TAG_TYPE_AUDIO, 23, &[0xaf, 0, 0x13, 0x88]).unwrap();
+        flv.write_raw_tag(TAG_TYPE_AUDIO, 23, &[0xaf, 1, 0x01]).unwrap();
+        let flv = flv.into_inner();
+
+        let aac = extract::adts::extract_adts(&flv[..], Vec::new()).unwrap();
+        assert_eq!(aac, [
+            0xff, 0xf1, 0x50, 0x80, 0x01, 0x3f, 0xfc, 0x21, 0x00,
+            0xff, 0xf1, 0x5c, 0x40, 0x01, 0x1f, 0xfc, 0x01,
+        ]);
+
+        // ADTS has no room for an escaped object type
+        let mut flv = writer::FlvWriter::new(Vec::new(), &header::FLVHeader {
+            version: 1,
+            audio: true,
+            video: false,
+            data_offset: 9,
+        }).unwrap();
+        flv.write_raw_tag(TAG_TYPE_AUDIO, 0, &[0xaf, 0, 0xf9, 0x5e, 0x01, 0x86, 0xa0, 0x40]).unwrap();
+        flv.write_raw_tag(TAG_TYPE_AUDIO, 0, &[0xaf, 1, 0x21]).unwrap();
+        let flv = flv.into_inner();
+        assert_eq!(
+            extract::adts::extract_adts(&flv[..], Vec::new()),
+            Err(Error::Unsupported { what: "AAC object type in ADTS", offset: 0 })
+        );
+
+        // no AAC audio in the test assets
+        assert_eq!(extract::adts::extract_adts(ZELDA, Vec::new()), Ok(vec![]));
+    }
+
+    #[test]
+    fn extract_mp3() {
+        use crate::tag::audio::mp3::{mpeg_audio_frame_header, ChannelMode, MpegAudioFrameHeader, MpegLayer, MpegVersion};
+
+        // MPEG-1 layer 3, 128 kbit/s, 48 kHz stereo: 384 bytes
+        let mut frame = vec![0; 384];
+        frame[..4].copy_from_slice(&[0xff, 0xfb, 0x94, 0x00]);
+        let header = mpeg_audio_frame_header(&frame).unwrap();
+        assert_eq!(header, MpegAudioFrameHeader {
+            version: MpegVersion::Mpeg1,
+            layer: MpegLayer::Layer3,
+            protected: false,
+            bitrate: 128,
+            sample_rate: 48000,
+            padding: false,
+            channel_mode: ChannelMode::Stereo,
+        });
+        assert_eq!(header.frame_size(), 384);
+        assert_eq!(header.samples_per_frame(), 1152);
+
+        // MPEG-2.5 layer 3, 8 kbit/s, 8 kHz mono: 72 bytes
+        let mut low = vec![0; 72];
+        low[..4].copy_from_slice(&[0xff, 0xe3, 0x18, 0xc0]);
+        let header = mpeg_audio_frame_header(&low).unwrap();
+        assert_eq!((header.sample_rate, header.channels(), header.frame_size()), (8000, 1, 72));
+
+        let mut flv = writer::FlvWriter::new(Vec::new(), &header::FLVHeader {
+            version: 1,
+            audio: true,
+            video: false,
+            data_offset: 9,
+        }).unwrap();
+        // the first frame spans two tags
+        flv.write_raw_tag(TAG_TYPE_AUDIO, 0, &[&[0x2f][..], &frame[..200]].concat()).unwrap();
+        flv.write_raw_tag(TAG_TYPE_AUDIO, 0, &[&[0x2f][..], &frame[200..]].concat()).unwrap();
+        flv.write_raw_tag(TAG_TYPE_AUDIO, 24, &[&[0x2f][..], &frame[..]].concat()).unwrap();
+        let flv = flv.into_inner();
+
+        let (tag, _) = tag::tag(&flv[13..]).unwrap();
+        match &tag.data {
+            tag::TagData::Audio(audio) => {
+                // SoundRate can only say 44 kHz
+                assert_eq!(audio.sound_rate, SoundRate::_44KHZ);
+                assert_eq!(audio.mp3_frame_header().unwrap().unwrap().sample_rate, 48000);
+            },
+            other => panic!("expected audio, got {:?}", other),
+        }
+
+        let mp3 = extract::mp3::extract_mp3(&flv[..], Vec::new()).unwrap();
+        assert_eq!(mp3, [&frame[..], &frame[..]].concat());
+
+        // the second frame has lost its sync
+        let mut flv = writer::FlvWriter::new(Vec::new(), &header::FLVHeader {
+            version: 1,
+            audio: true,
+            video: false,
+            data_offset: 9,
+        }).unwrap();
+        flv.write_raw_tag(TAG_TYPE_AUDIO, 0, &[&[0x2f][..], &frame[..], &[0xfe, 0xfb, 0x94, 0x00]].concat()).unwrap();
+        let flv = flv.into_inner();
+        assert_eq!(
+            extract::mp3::extract_mp3(&flv[..], Vec::new()),
+            Err(Error::BadSignature { what: "MPEG audio frame sync", offset: 384 })
+        );
+        assert_eq!(
+            mpeg_audio_frame_header(&[0xff, 0xfb, 0xf4, 0x00]),
+            Err(Error::Unsupported { what: "invalid MPEG audio bitrate", offset: 2 })
+        );
+    }
+
+    #[test]
+    fn extract_wav() {
+        use crate::tag::audio::adpcm::decode_adpcm;
+
+        // 2 bit codes, first sample 1000 at step index 0, then the codes 1, 3, 0 and 2
+        assert_eq!(decode_adpcm(&[0x00, 0xfa, 0x00, 0x72], 1), Ok(vec![1000, 1010, 997, 1002, 997]));
+        assert!(matches!(decode_adpcm(&[], 1), Err(Error::Truncated { .. })));
+
+        let mut flv = writer::FlvWriter::new(Vec::new(), &header::FLVHeader {
+            version: 1,
+            audio: true,
+            video: false,
+            data_offset: 9,
+        }).unwrap();
+        // 16 bit little endian stereo at 11 kHz
+        flv.write_raw_tag(TAG_TYPE_AUDIO, 0, &[0x37, 1, 0, 2, 0]).unwrap();
+        flv.write_raw_tag(TAG_TYPE_AUDIO, 0, &[0x37, 3, 0, 4, 0]).unwrap();
+        let flv = flv.into_inner();
+        let wav = extract::wav::extract_wav(&flv[..], Cursor::new(Vec::new())).unwrap().into_inner();
+        assert_eq!(wav, [
+            &b"RIFF"[..], &[44, 0, 0, 0], b"WAVE",
+            b"fmt ", &[16, 0, 0, 0], &[1, 0], &[2, 0], &11025u32.to_le_bytes(), &44100u32.to_le_bytes(), &[4, 0], &[16, 0],
+            b"data", &[8, 0, 0, 0], &[1, 0, 2, 0, 3, 0, 4, 0],
+        ].concat());
+
+        // the header is filled in where it was written, after whatever the output held before
+        let mut out = Cursor::new(b"ab".to_vec());
+        out.set_position(2);
+        let appended = extract::wav::extract_wav(&flv[..], out).unwrap().into_inner();
+        assert_eq!(appended, [&b"ab"[..], &wav].concat());
+
+        // the zelda samples are ADPCM, decoded to 16 bit mono at 22 kHz
+        let wav = extract::wav::extract_wav(ZELDA, Cursor::new(Vec::new())).unwrap().into_inner();
+        assert_eq!(&wav[..4], b"RIFF");
+        assert_eq!(&wav[22..24], &[1, 0]);
+        assert_eq!(&wav[24..28], &22050u32.to_le_bytes());
+        assert_eq!(&wav[34..36], &[16, 0]);
+        let data_size = u32::from_le_bytes([wav[40], wav[41], wav[42], wav[43]]) as usize;
+        assert!(data_size > 0);
+        assert_eq!(wav.len(), 44 + data_size);
+
+        // nothing to write without PCM or ADPCM audio
+        let mut flv = writer::FlvWriter::new(Vec::new(), &header::FLVHeader {
+            version: 1,
+            audio: true,
+            video: false,
+            data_offset: 9,
+        }).unwrap();
+        flv.write_raw_tag(TAG_TYPE_AUDIO, 0, &[0xaf, 1, 0x21]).unwrap();
+        let flv = flv.into_inner();
+        assert_eq!(extract::wav::extract_wav(&flv[..], Cursor::new(Vec::new())).unwrap().into_inner(), vec![]);
+    }
+
+    #[test]
+    fn g711_audio_tags() {
+        use crate::tag::audio::{audio_header, write_audio_header};
+
+        for (b, format) in [
+            (0x70, SoundFormat::G711ALaw),
+            (0x80, SoundFormat::G711MuLaw),
+            (0xe0, SoundFormat::MP38KHZ),
+            (0xf0, SoundFormat::DeviceSpecific),
+        ] {
+            let (sound_format, sound_rate, sound_size, sound_type) = audio_header(b);
+            assert_eq!(sound_format, format);
+            assert_eq!(write_audio_header(&sound_format, &sound_rate, &sound_size, &sound_type), b);
+        }
+
+        // the SoundRate of G.711 is ignored, it is always 8 kHz
+        let audio = tag::audio::audio_data(&[0x8c, 0xff, 0x7f], 3).unwrap();
+        assert_eq!(audio.sound_rate, SoundRate::_44KHZ);
+        assert_eq!((audio.sample_rate(), audio.channels()), (8000, 1));
+
+        let mut flv = writer::FlvWriter::new(Vec::new(), &header::FLVHeader {
+            version: 1,
+            audio: true,
+            video: false,
+            data_offset: 9,
+        }).unwrap();
+        flv.write_raw_tag(TAG_TYPE_AUDIO, 0, &[0x8c, 0xff, 0x7f, 0x00]).unwrap();
+        let flv = flv.into_inner();
+        let wav = extract::wav::extract_wav(&flv[..], Cursor::new(Vec::new())).unwrap().into_inner();
+        assert_eq!(wav, [
+            &b"RIFF"[..], &[54, 0, 0, 0], b"WAVE",
+            b"fmt ", &[18, 0, 0, 0], &[7, 0], &[1, 0], &8000u32.to_le_bytes(), &8000u32.to_le_bytes(), &[1, 0], &[8, 0], &[0, 0],
+            b"fact", &[4, 0, 0, 0], &[3, 0, 0, 0],
+            b"data", &[3, 0, 0, 0], &[0xff, 0x7f, 0x00, 0],
+        ].concat());
+    }
+
+    #[test]
+    fn unknown_tags() {
+        use crate::tag::{TagData, UnknownTagPolicy};
+
+        let mut flv = writer::FlvWriter::new(Vec::new(), &header::FLVHeader {
+            version: 1,
+            audio: true,
+            video: false,
+            data_offset: 9,
+        }).unwrap();
+        flv.write_raw_tag(0x10, 5, &[0x17, 0, 1]).unwrap();
+        flv.write_raw_tag(TAG_TYPE_AUDIO, 10, &[0x2e, 0xff]).unwrap();
+        let flv = flv.into_inner();
+
+        // kept by default, and not mistaken for a video tag
+        let file = file::flv_file(&flv[..]).unwrap();
+        assert_eq!(file.tags.len(), 2);
+        assert_eq!(file.tags[0].header.tag_type, 0x10);
+        assert_eq!(file.tags[0].data, TagData::Unknown { tag_type: 0x10, payload: vec![0x17, 0, 1] });
+
+        let mut out = writer::FlvWriter::new(Vec::new(), &file.header).unwrap();
+        for tag in &file.tags {
+            out.write_tag(tag).unwrap();
+        }
+        assert_eq!(out.into_inner(), flv);
+
+        let mut tags = file::Tags::new(&flv[..]).unwrap();
+        tags.set_unknown_tag_policy(UnknownTagPolicy::Skip);
+        let tags = tags.collect::<Result<Vec<_>, _>>().unwrap();
+        assert_eq!(tags, file.tags[1..]);
+        let mut tags = file::Tags::new(&flv[..]).unwrap();
+        tags.set_unknown_tag_policy(UnknownTagPolicy::Error);
+        assert_eq!(tags.next(), Some(Err(Error::UnknownTagType { tag_type: 0x10, offset: 13 })));
+        assert_eq!(tags.next(), None);
+
+        let mut reader = reader::FlvReader::new(&flv[..]).unwrap();
+        reader.set_unknown_tag_policy(UnknownTagPolicy::Skip);
+        let tags = reader.collect::<Result<Vec<_>, _>>().unwrap();
+        assert_eq!(tags, file.tags[1..]);
+        let mut reader = reader::FlvReader::new(&flv[..]).unwrap();
+        reader.set_unknown_tag_policy(UnknownTagPolicy::Error);
+        assert_eq!(reader.next(), Some(Err(Error::UnknownTagType { tag_type: 0x10, offset: 13 })));
+
+        let mut decoder = decoder::FlvDecoder::new();
+        decoder.set_unknown_tag_policy(UnknownTagPolicy::Skip);
+        decoder.push(&flv);
+        assert!(matches!(decoder.poll(), Ok(decoder::Decoded::Header(_))));
+        assert!(matches!(decoder.poll(), Ok(decoder::Decoded::Tag(tag)) if tag == file.tags[1]));
+        let mut decoder = decoder::FlvDecoder::new();
+        decoder.set_unknown_tag_policy(UnknownTagPolicy::Error);
+        decoder.push(&flv);
+        assert!(matches!(decoder.poll(), Ok(decoder::Decoded::Header(_))));
+        assert_eq!(decoder.poll(), Err(Error::UnknownTagType { tag_type: 0x10, offset: 13 }));
+        assert!(matches!(decoder.poll(), Ok(decoder::Decoded::Tag(tag)) if tag == file.tags[1]));
+    }
+
+    #[test]
+    fn script_data() {
+        use crate::tag::amf0::{AMFData, AMFObject, ScriptData};
+        use crate::tag::TagData;
+
+        let flv = file::flv_file(COMMERCIAL).unwrap();
+        let TagData::Script(data) = &flv.tags[0].data else { panic!("not a script tag") };
+        assert_eq!(data.name, "onMetaData");
+        assert_eq!(data.values.len(), 1);
+        assert_eq!(data.meta_data().unwrap().duration, Some(28.133));
+
+        // every value is kept, and the body is written back as it was
+        let set_data_frame = ScriptData {
+            name: "@setDataFrame".to_string(),
+            values: vec![
+                AMFData::String("onMetaData".to_string()),
+                AMFData::Mixedarray(vec![AMFObject { name: "width".to_string(), data: AMFData::Number(640.0) }]),
+            ],
+        };
+        let mut body = Vec::new();
+        tag::amf0::write_script_data(&mut body, &set_data_frame).unwrap();
+        assert_eq!(tag::amf0::script_data(&body), Ok(set_data_frame));
+        body.push(0xff);
+        assert_eq!(tag::amf0::script_data(&body), Err(Error::InvalidAmfMarker { marker: 0xff, offset: body.len() - 1 }));
+
+        fn object(properties: &[(&str, AMFData)]) -> AMFData {
+            AMFData::Object(properties.iter().map(|(name, data)| AMFObject { name: name.to_string(), data: data.clone() }).collect())
+        }
+        let cue_point = ScriptData {
+            name: "onCuePoint".to_string(),
+            values: vec![object(&[
+                ("name", AMFData::String("chapter 2".to_string())),
+                ("time", AMFData::Number(12.5)),
+                ("type", AMFData::String("navigation".to_string())),
+                ("parameters", object(&[("title", AMFData::String("Intro".to_string()))])),
+            ])],
+        };
+        let cue_point = cue_point.cue_point().unwrap();
+        assert_eq!((cue_point.name.as_str(), cue_point.time), ("chapter 2", 12.5));
+        assert_eq!(cue_point.cue_point_type.as_deref(), Some("navigation"));
+        assert_eq!(cue_point.parameters["title"], AMFData::String("Intro".to_string()));
+
+        let text = ScriptData {
+            name: "onTextData".to_string(),
+            values: vec![AMFData::Mixedarray(vec![
+                AMFObject { name: "text".to_string(), data: AMFData::String("Hello".to_string()) },
+                AMFObject { name: "trackid".to_string(), data: AMFData::Number(1.0) },
+            ])],
+        };
+        let text_data = text.text_data().unwrap();
+        assert_eq!((text_data.text.as_str(), text_data.track_id), ("Hello", Some(1.0)));
+        assert_eq!(text.caption(), None);
+        assert_eq!(text.cue_point(), None);
+        assert_eq!(ScriptData { name: "onCaption".to_string(), ..text }.caption(), Some(text_data));
+
+        let frame_info = ScriptData {
+            name: "onFI".to_string(),
+            values: vec![object(&[
+                ("sd", AMFData::String("18-10-2026".to_string())),
+                ("st", AMFData::String("14:03:27.120".to_string())),
+            ])],
+        };
+        let frame_info = frame_info.frame_info().unwrap();
+        assert_eq!(frame_info.system_time.as_deref(), Some("14:03:27.120"));
+        assert_eq!(frame_info.timecode, None);
+    }
+
+    #[test]
+    fn amf0_markers() {
+        use crate::tag::amf0::{AMFData, AMFObject, AMFTypedObject};
+
+        let values = vec![
+            AMFData::MovieClip,
+            AMFData::Unsupported,
+            AMFData::RecordSet,
+            AMFData::XmlDocument("<a b=\"c\"/>".to_string()),
+            AMFData::TypedObject(AMFTypedObject {
+                class_name: "flex.messaging.messages.AcknowledgeMessage".to_string(),
+                properties: vec![AMFObject { name: "messageId".to_string(), data: AMFData::String("1".to_string()) }],
+            }),
+        ];
+        let mut out = Vec::new();
+        for value in &values {
+            tag::amf0::write_amf_data_value(&mut out, value).unwrap();
+        }
+        assert_eq!(&out[..3], &[0x04, 0x0d, 0x0e]);
+        assert_eq!(&out[3..8], &[0x0f, 0, 0, 0, 10]);
+        let mut last = &out[..];
+        for value in values {
+            let decoded;
+            (decoded, last) = tag::amf0::amf_data_value(last).unwrap();
+            assert_eq!(decoded, value);
+        }
+        assert!(last.is_empty());
+
+        // an AMF3 integer after an AVM+ switch, inside an object
+        let switched = [0x03, 0, 1, b'n', 0x11, 0x04, 0x05, 0, 0, 0x09];
+        let value = AMFData::Object(vec![AMFObject {
+            name: "n".to_string(),
+            data: AMFData::AvmPlus(tag::amf3::Amf3Value::Integer(5)),
+        }]);
+        assert_eq!(tag::amf0::amf_data_value(&switched), Ok((value.clone(), &b""[..])));
+        let mut out = Vec::new();
+        tag::amf0::write_amf_data_value(&mut out, &value).unwrap();
+        assert_eq!(out, switched);
+        fn amf3_integer(input: &[u8]) -> Result<(AMFData, &[u8]), Error> {
+            match input {
+                [0x04, n, last @ ..] if n & 0x80 == 0 => Ok((AMFData::Number(*n as f64), last)),
+                _ => Err(Error::Unsupported { what: "AMF3 value", offset: 0 }),
+            }
+        }
+        assert_eq!(
+            tag::amf0::amf_data_value_with_amf3(&switched, amf3_integer),
+            Ok((AMFData::Object(vec![AMFObject { name: "n".to_string(), data: AMFData::Number(5.0) }]), &b""[..]))
+        );
+    }
+
+    #[test]
+    fn amf3() {
+        use crate::tag::amf3::{self, Amf3Array, Amf3Member, Amf3Object, Amf3Traits, Amf3Value, Amf3Vector};
+
+        fn round_trip(bytes: &[u8], value: Amf3Value) {
+            assert_eq!(amf3::amf3_value(bytes), Ok((value.clone(), &b""[..])));
+            let mut out = Vec::new();
+            amf3::write_amf3_value(&mut out, &value).unwrap();
+            assert_eq!(out, bytes);
+        }
+
+        round_trip(&[0x04, 0x7f], Amf3Value::Integer(127));
+        round_trip(&[0x04, 0x81, 0x00], Amf3Value::Integer(128));
+        round_trip(&[0x04, 0xff, 0xff, 0xff, 0xff], Amf3Value::Integer(-1));
+        round_trip(&[0x04, 0xbf, 0xff, 0xff, 0xff], Amf3Value::Integer(amf3::AMF3_INTEGER_MAX));
+        let mut out = Vec::new();
+        amf3::write_amf3_value(&mut out, &Amf3Value::Integer(1 << 28)).unwrap();
+        assert_eq!(out, [&[0x05][..], &268435456f64.to_be_bytes()].concat());
+
+        round_trip(&[0x08, 0x01, 0, 0, 0, 0, 0, 0, 0, 0], Amf3Value::Date(0.0));
+        round_trip(&[0x0c, 0x05, 1, 2], Amf3Value::ByteArray(vec![1, 2]));
+        round_trip(&[0x0b, 0x09, b'<', b'a', b'/', b'>'], Amf3Value::Xml("<a/>".to_string()));
+        round_trip(&[0x0d, 0x03, 0x01, 0xff, 0xff, 0xff, 0xfe], Amf3Value::VectorInt(Amf3Vector { fixed: true, values: vec![-2] }));
+
+        // the second string is a reference to the first one
+        round_trip(&[0x09, 0x05, 0x01, 0x06, 0x07, b'a', b'b', b'c', 0x06, 0x00], Amf3Value::Array(Amf3Array {
+            associative: vec![],
+            dense: vec![Amf3Value::String("abc".to_string()), Amf3Value::String("abc".to_string())],
+        }));
+
+        // an object reference is copied, then written in full with references to its traits and names
+        let object = Amf3Value::Object(Amf3Object {
+            traits: Amf3Traits { dynamic: true, ..Default::default() },
+            dynamic_members: vec![Amf3Member { name: "x".to_string(), value: Amf3Value::Integer(1) }],
+            ..Default::default()
+        });
+        let array = Amf3Value::Array(Amf3Array { associative: vec![], dense: vec![object.clone(), object] });
+        let referenced = [0x09, 0x05, 0x01, 0x0a, 0x0b, 0x01, 0x03, b'x', 0x04, 0x01, 0x01, 0x0a, 0x02];
+        assert_eq!(amf3::amf3_value(&referenced), Ok((array.clone(), &b""[..])));
+        round_trip(&[&referenced[..11], &[0x0a, 0x01, 0x00, 0x04, 0x01, 0x01]].concat(), array);
+
+        // equal byte arrays and dates are written as references, counting the array and object before them
+        let empty = Amf3Value::Object(Amf3Object { traits: Amf3Traits { dynamic: true, ..Default::default() }, ..Default::default() });
+        let bytes = Amf3Value::ByteArray(vec![1]);
+        let date = Amf3Value::Date(1.0);
+        round_trip(
+            &[&[0x09, 0x0b, 0x01, 0x0a, 0x0b, 0x01, 0x01, 0x0c, 0x03, 0x01, 0x08, 0x01][..], &1f64.to_be_bytes(), &[0x0c, 0x04, 0x08, 0x06]].concat(),
+            Amf3Value::Array(Amf3Array { associative: vec![], dense: vec![empty, bytes.clone(), date.clone(), bytes, date] }),
+        );
+
+        let mut sealed = Vec::new();
+        let point = Amf3Value::Object(Amf3Object {
+            traits: Amf3Traits {
+                class_name: "Point".to_string(),
+                sealed_names: vec!["x".to_string(), "y".to_string()],
+                ..Default::default()
+            },
+            sealed_values: vec![Amf3Value::Double(0.5), Amf3Value::Null],
+            ..Default::default()
+        });
+        amf3::write_amf3_value(&mut sealed, &point).unwrap();
+        assert_eq!(&sealed[..2], &[0x0a, 0x23]);
+        assert_eq!(amf3::amf3_value(&sealed), Ok((point, &b""[..])));
+
+        let collection = [&[0x0a, 0x07, 0x43][..], b"flex.messaging.io.ArrayCollection", &[0x09, 0x03, 0x01, 0x04, 0x05]].concat();
+        let (value, _) = amf3::amf3_value(&collection).unwrap();
+        let Amf3Value::Object(object) = &value else { panic!("not an object") };
+        assert!(object.traits.externalizable);
+        assert_eq!(object.external.as_deref(), Some(&Amf3Value::Array(Amf3Array { associative: vec![], dense: vec![Amf3Value::Integer(5)] })));
+        round_trip(&collection, value);
+        assert_eq!(
+            amf3::amf3_value(&[0x0a, 0x07, 0x03, b'A']),
+            Err(Error::Unsupported { what: "AMF3 externalizable class", offset: 4 })
+        );
+
+        assert_eq!(amf3::amf3_value(&[0x06, 0x02]), Err(Error::InvalidAmfReference { index: 1, offset: 1 }));
+        assert_eq!(
+            amf3::amf3_value(&[0x09, 0x03, 0x01, 0x09, 0x00]),
+            Err(Error::Unsupported { what: "AMF3 cyclic reference", offset: 4 })
+        );
+        assert_eq!(amf3::amf3_value(&[0x12]), Err(Error::InvalidAmfMarker { marker: 0x12, offset: 0 }));
+
+        // each array holds the previous one twice
+        let mut bombs = vec![0x09, 30 << 1 | 1, 0x01, 0x09, 0x01, 0x01];
+        for k in 1..30 {
+            bombs.extend_from_slice(&[0x09, 0x05, 0x01, 0x09, k << 1, 0x09, k << 1]);
+        }
+        assert!(matches!(amf3::amf3_value(&bombs), Err(Error::Unsupported { .. })));
+
+        // each string reference copies 4 KB
+        let mut strings = vec![0x09, 0xce, 0x11, 0x01, 0x06, 0xc0, 0x01];
+        strings.extend_from_slice(&[b'a'; 4096]);
+        for _ in 1..5000 {
+            strings.extend_from_slice(&[0x06, 0x00]);
+        }
+        assert_eq!(
+            amf3::amf3_value(&strings),
+            Err(Error::Unsupported { what: "AMF3 value expanding past AMF3_MAX_BYTES", offset: 4104 + 2 * 4095 })
+        );
+    }
 }
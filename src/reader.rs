@@ -0,0 +1,146 @@
+use std::io::{ErrorKind, Read};
+
+use crate::file::PREVIOUS_TAG_SIZE;
+use crate::header::{self, FLVHeader, FLV_HEADER_SIZE};
+use crate::tag::{self, Tag, UnknownTagPolicy, TAG_HEADER_SIZE};
+use crate::Error;
+
+// Reads FLV tags one at a time from any byte source (file, pipe, socket).
+// Only the tag being parsed is held in memory, so the source can be of any length.
+// The reader issues many small reads: wrap unbuffered sources in a `BufReader`.
+// Error offsets are counted from the first byte read.
+pub struct FlvReader<R: Read> {
+    inner: R,
+    header: FLVHeader,
+    // number of bytes read from `inner` so far
+    position: usize,
+    // holds the TagHeader and data of the current tag, reused between tags
+    buf: Vec<u8>,
+    unknown_tags: UnknownTagPolicy,
+    done: bool,
+}
+
+impl<R: Read> FlvReader<R> {
+    // Reads the FLV header, then skips to `data_offset`.
+    // Blocks until the header is available.
+    pub fn new(mut inner: R) -> Result<Self, Error> {
+        let mut buf = [0u8; FLV_HEADER_SIZE];
+        if fill(&mut inner, &mut buf)? < FLV_HEADER_SIZE {
+            return Err(Error::Truncated { needed: FLV_HEADER_SIZE, offset: 0 });
+        }
+        let header = header::flv_header(&buf)?;
+
+        let extra = (header.data_offset as usize).saturating_sub(FLV_HEADER_SIZE);
+        let skipped = std::io::copy(&mut (&mut inner).take(extra as u64), &mut std::io::sink())?;
+        if skipped < extra as u64 {
+            return Err(Error::Truncated { needed: extra, offset: FLV_HEADER_SIZE });
+        }
+
+        Ok(FlvReader {
+            inner,
+            header,
+            position: FLV_HEADER_SIZE + extra,
+            buf: Vec::new(),
+            unknown_tags: UnknownTagPolicy::default(),
+            done: false,
+        })
+    }
+
+    pub fn header(&self) -> &FLVHeader {
+        &self.header
+    }
+
+    // Unknown tags are kept by default
+    pub fn set_unknown_tag_policy(&mut self, policy: UnknownTagPolicy) {
+        self.unknown_tags = policy;
+    }
+
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+
+    // Number of bytes read from the source so far
+    pub fn position(&self) -> usize {
+        self.position
+    }
+
+    fn fill(&mut self, buf: &mut [u8]) -> Result<usize, Error> {
+        let n = fill(&mut self.inner, buf)?;
+        self.position += n;
+        Ok(n)
+    }
+
+    // Reads the PreviousTagSize before the tag, then the tag itself.
+    // Returns None if the source ends cleanly between two tags.
+    fn next_tag(&mut self) -> Result<Option<Tag>, Error> {
+        loop {
+            let start = self.position;
+            let mut previous_tag_size = [0u8; PREVIOUS_TAG_SIZE];
+            match self.fill(&mut previous_tag_size)? {
+                0 => return Ok(None),
+                PREVIOUS_TAG_SIZE => {},
+                _ => return Err(Error::Truncated { needed: PREVIOUS_TAG_SIZE, offset: start }),
+            }
+
+            let start = self.position;
+            let mut buf = std::mem::take(&mut self.buf);
+            buf.resize(TAG_HEADER_SIZE, 0);
+            match self.fill(&mut buf)? {
+                0 => return Ok(None),
+                TAG_HEADER_SIZE => {},
+                _ => return Err(Error::Truncated { needed: TAG_HEADER_SIZE, offset: start }),
+            }
+            let header = tag::tag_header(&buf).map_err(|e| e.shift(start))?;
+
+            let tag_size = TAG_HEADER_SIZE + header.data_size as usize;
+            buf.resize(tag_size, 0);
+            if self.fill(&mut buf[TAG_HEADER_SIZE..])? < header.data_size as usize {
+                return Err(Error::Truncated { needed: tag_size, offset: start });
+            }
+            if !tag::is_known_tag_type(header.tag_type) {
+                match self.unknown_tags {
+                    UnknownTagPolicy::Error => {
+                        return Err(Error::UnknownTagType { tag_type: header.tag_type, offset: start });
+                    },
+                    UnknownTagPolicy::Skip => {
+                        self.buf = buf;
+                        continue;
+                    },
+                    UnknownTagPolicy::Keep => {},
+                }
+            }
+            let res = tag::tag(&buf).map(|(tag, _)| tag).map_err(|e| e.shift(start));
+            self.buf = buf;
+            return res.map(Some);
+        }
+    }
+}
+
+impl<R: Read> Iterator for FlvReader<R> {
+    type Item = Result<Tag, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        let res = self.next_tag();
+        if !matches!(res, Ok(Some(_))) {
+            self.done = true;
+        }
+        res.transpose()
+    }
+}
+
+// Fills `buf` unless the source ends first, returns how many bytes were read
+fn fill<R: Read>(inner: &mut R, buf: &mut [u8]) -> Result<usize, Error> {
+    let mut n = 0;
+    while n < buf.len() {
+        match inner.read(&mut buf[n..]) {
+            Ok(0) => break,
+            Ok(len) => n += len,
+            Err(e) if e.kind() == ErrorKind::Interrupted => {},
+            Err(e) => return Err(e.into()),
+        }
+    }
+    Ok(n)
+}
@@ -1,6 +1,10 @@
 pub mod audio;
 pub mod video;
 pub mod amf0;
+pub mod amf3;
+pub mod encryption;
+
+use crate::Error;
 
 pub const TAG_HEADER_SIZE: usize = 11;
 pub const TAG_TYPE_AUDIO: u8 = 8;
@@ -10,7 +14,11 @@ pub const TAG_TYPE_SCRIPT: u8 = 18;
 // TagHeader: This part has a definite size, so as header
 #[derive(Debug, PartialEq, Clone)]
 pub struct TagHeader {
-    // Type of this tag. Values are:
+    // [bit;2] reserved, must be 0 but kept as found so the tag is written back unchanged
+    pub reserved: u8,
+    // [bit;1] Filter: the data must be decrypted before use
+    pub filter: bool,
+    // [bit;5] Type of this tag. Values are:
     // 8: audio
     // 9: video
     // 18: script data
@@ -32,14 +40,65 @@ pub struct Tag {
     pub data: TagData, // Body of the tag
 }
 
+impl Tag {
+    // Decoding timestamp in milliseconds
+    pub fn dts(&self) -> u32 {
+        self.header.timestamp
+    }
+
+    // Presentation timestamp in milliseconds: the timestamp plus the composition time of AVC or HEVC video.
+    // The same as `dts` for every other tag.
+    pub fn pts(&self) -> i64 {
+        let composition_time = match &self.data {
+            TagData::Video(video::VideoData {
+                video_data: video::VideoDataByFrame::VideoFramePayload(payload),
+                ..
+            }) => match payload {
+                video::VideoPacketData::AVCVideoPacket(packet) => packet.composition_time,
+                video::VideoPacketData::ExVideoPacket(packet) => packet.composition_time,
+                _ => 0,
+            },
+            TagData::Encrypted(encryption::EncryptedData {
+                media_header: encryption::MediaHeader::Video(header),
+                ..
+            }) => header.composition_time,
+            _ => 0,
+        };
+        self.header.timestamp as i64 + composition_time as i64
+    }
+}
+
 #[derive(Debug,PartialEq)]
 pub enum TagData {
     Audio(audio::AudioData),
     Video(video::VideoData),
-    Script(amf0::AMFObject),
+    Script(amf0::ScriptData),
+    // the Filter bit is set
+    Encrypted(encryption::EncryptedData),
+    // a reserved tag type, kept as it is
+    Unknown { tag_type: u8, payload: Vec<u8> },
+}
+
+// What the readers do with tags of a type other than audio, video or script data
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+pub enum UnknownTagPolicy {
+    // fail with `Error::UnknownTagType`
+    Error,
+    // leave them out
+    Skip,
+    // return them as `TagData::Unknown`
+    #[default]
+    Keep,
+}
+
+pub fn is_known_tag_type(tag_type: u8) -> bool {
+    matches!(tag_type, TAG_TYPE_AUDIO | TAG_TYPE_VIDEO | TAG_TYPE_SCRIPT)
 }
 
-pub fn tag_header(input: &[u8]) -> Result<TagHeader, String> {
+pub fn tag_header(input: &[u8]) -> Result<TagHeader, Error> {
+    if input.len() < TAG_HEADER_SIZE {
+        return Err(Error::Truncated { needed: TAG_HEADER_SIZE, offset: 0 });
+    }
     let data_size = u32::from_be_bytes([
         0,
         input[1],
@@ -60,41 +119,122 @@ pub fn tag_header(input: &[u8]) -> Result<TagHeader, String> {
     ]);
 
     Ok(TagHeader{
-        tag_type: input[0],
+        reserved: input[0] >> 6,
+        filter: input[0] & 0b10_0000 != 0,
+        tag_type: input[0] & 0b1_1111,
         data_size,
         timestamp,
         stream_id,
     })
 }
 
-pub fn tag(input: &[u8]) -> Result<(Tag, &[u8]), String> {
-    let header = tag_header(&input[..TAG_HEADER_SIZE])?;
+// Errors in the tag data have their offset counted from the start of the TagHeader
+pub fn tag(input: &[u8]) -> Result<(Tag, &[u8]), Error> {
+    let header = tag_header(input)?;
+    let tag_size = TAG_HEADER_SIZE + header.data_size as usize;
+    if input.len() < tag_size {
+        return Err(Error::Truncated { needed: tag_size, offset: 0 });
+    }
+    let body = &input[TAG_HEADER_SIZE..tag_size];
     let data = match header.tag_type {
+        TAG_TYPE_AUDIO | TAG_TYPE_VIDEO | TAG_TYPE_SCRIPT if header.filter => {
+            let data = encryption::encrypted_data(body, header.tag_type)
+                .map_err(|e| e.shift(TAG_HEADER_SIZE))?;
+            TagData::Encrypted(data)
+        },
         TAG_TYPE_AUDIO => {
-            let data = audio::audio_data(&input[TAG_HEADER_SIZE..], header.data_size as usize)?;
+            let data = audio::audio_data(body, header.data_size as usize)
+                .map_err(|e| e.shift(TAG_HEADER_SIZE))?;
             TagData::Audio(data)
         },
         TAG_TYPE_VIDEO => {
-            let data = video::video_data(&input[TAG_HEADER_SIZE..], header.data_size as usize)?;
+            let data = video::video_data(body, header.data_size as usize)
+                .map_err(|e| e.shift(TAG_HEADER_SIZE))?;
             TagData::Video(data)
         },
         TAG_TYPE_SCRIPT => {
-            let (data, _) = amf0::amf_data(&input[TAG_HEADER_SIZE..])?;
+            let data = amf0::script_data(body).map_err(|e| e.shift(TAG_HEADER_SIZE))?;
             TagData::Script(data)
         },
-        _ => {
-            // TODO:
-            let data = video::video_data(&input[TAG_HEADER_SIZE..], header.data_size as usize)?;
-            TagData::Video(data)
-        }
+        tag_type => TagData::Unknown {
+            tag_type,
+            payload: Vec::from(body),
+        },
     };
 
-    let offset = header.data_size as usize;
     Ok((
         Tag{
             header,
             data,
         },
-        &input[offset + TAG_HEADER_SIZE..]
+        &input[tag_size..]
         ))
 }
+
+// `tag_type` must fit in 5 bits, `data_size` in 24 bits and `stream_id` in 24 bits
+pub fn write_tag_header(out: &mut Vec<u8>, header: &TagHeader) -> Result<(), Error> {
+    if header.reserved > 0b11 {
+        return Err(Error::TooLarge { what: "tag header reserved bits", size: header.reserved as usize });
+    }
+    if header.tag_type > 0b1_1111 {
+        return Err(Error::TooLarge { what: "tag type", size: header.tag_type as usize });
+    }
+    if header.data_size > 0xff_ffff {
+        return Err(Error::TooLarge { what: "tag data", size: header.data_size as usize });
+    }
+    if header.stream_id > 0xff_ffff {
+        return Err(Error::TooLarge { what: "stream id", size: header.stream_id as usize });
+    }
+    let data_size = header.data_size.to_be_bytes();
+    let timestamp = header.timestamp.to_be_bytes();
+    let stream_id = header.stream_id.to_be_bytes();
+    out.extend_from_slice(&[
+        header.reserved << 6 | (header.filter as u8) << 5 | header.tag_type,
+        data_size[1],
+        data_size[2],
+        data_size[3],
+        // the lower 24 bits, then the extended byte holding the upper 8
+        timestamp[1],
+        timestamp[2],
+        timestamp[3],
+        timestamp[0],
+        stream_id[1],
+        stream_id[2],
+        stream_id[3],
+    ]);
+    Ok(())
+}
+
+// Writes the TagHeader and the data of the tag.
+// `data_size` is taken from the encoded data, `tag_type` and `filter` from its kind, not from `tag.header`.
+// Unknown tags are written unchanged, with their own `tag_type` and the `filter` of `tag.header`.
+pub fn write_tag(out: &mut Vec<u8>, tag: &Tag) -> Result<(), Error> {
+    let mut body = Vec::new();
+    match &tag.data {
+        TagData::Audio(data) => audio::write_audio_data(&mut body, data),
+        TagData::Video(data) => video::write_video_data(&mut body, data)?,
+        TagData::Script(data) => amf0::write_script_data(&mut body, data)?,
+        TagData::Encrypted(data) => encryption::write_encrypted_data(&mut body, data)?,
+        TagData::Unknown { payload, .. } => body.extend_from_slice(payload),
+    }
+    let (tag_type, filter) = match &tag.data {
+        TagData::Audio(_) => (TAG_TYPE_AUDIO, false),
+        TagData::Video(_) => (TAG_TYPE_VIDEO, false),
+        TagData::Script(_) => (TAG_TYPE_SCRIPT, false),
+        TagData::Encrypted(data) => match data.media_header {
+            encryption::MediaHeader::Audio(_) => (TAG_TYPE_AUDIO, true),
+            encryption::MediaHeader::Video(_) => (TAG_TYPE_VIDEO, true),
+            encryption::MediaHeader::None => (TAG_TYPE_SCRIPT, true),
+        },
+        TagData::Unknown { tag_type, .. } => (*tag_type, tag.header.filter),
+    };
+    let data_size = u32::try_from(body.len()).map_err(|_| Error::TooLarge { what: "tag data", size: body.len() })?;
+    write_tag_header(out, &TagHeader {
+        tag_type,
+        filter,
+        data_size,
+        ..tag.header.clone()
+    })?;
+    out.extend_from_slice(&body);
+    Ok(())
+}
@@ -1,19 +1,80 @@
+use std::collections::HashMap;
 use std::f64;
 
-#[derive(Debug, PartialEq)]
+use crate::error::{consumed, take};
+use crate::tag::amf3::{self, Amf3Value};
+use crate::Error;
+
+// The content of an onMetaData script tag.
+// Every field is optional: encoders write whichever keys they like.
+#[derive(Debug, PartialEq, Clone, Default)]
 pub struct MetaData {
-    pub duration: f64,
-    pub width: f64,
-    pub height: f64,
+    // in seconds
+    pub duration: Option<f64>,
+    pub width: Option<f64>,
+    pub height: Option<f64>,
     // indicate the video bit rate in kilobits per second
-    pub video_data_rate: f64,
-    pub framerate: f64,
-    pub video_codec_id: f64,
-    pub audio_sample_rate: f64,
-    pub audio_sample_size: f64,
-    pub stereo: bool,
-    pub audio_codec_id: f64,
-    pub file_size: f64,
+    pub video_data_rate: Option<f64>,
+    pub framerate: Option<f64>,
+    pub video_codec_id: Option<f64>,
+    pub audio_sample_rate: Option<f64>,
+    pub audio_sample_size: Option<f64>,
+    pub stereo: Option<bool>,
+    pub audio_codec_id: Option<f64>,
+    pub file_size: Option<f64>,
+    // indicate the audio bit rate in kilobits per second
+    pub audio_data_rate: Option<f64>,
+    // in seconds
+    pub audio_delay: Option<f64>,
+    pub can_seek_to_end: Option<bool>,
+    pub creation_date: Option<String>,
+    pub encoder: Option<String>,
+    // in seconds
+    pub last_timestamp: Option<f64>,
+    pub keyframes: Option<KeyFrames>,
+    // keys not listed above, and known keys holding a value of an unexpected type
+    pub extra: HashMap<String, AMFData>,
+}
+
+// Seek points, written as an object of two arrays of the same length
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct KeyFrames {
+    // in seconds
+    pub times: Vec<f64>,
+    // offset from the start of the file of each keyframe tag
+    pub file_positions: Vec<f64>,
+}
+
+// An onCuePoint script tag, as written by Flash encoders for cue points embedded in the video
+#[derive(Debug, PartialEq, Clone)]
+pub struct CuePoint {
+    pub name: String,
+    // in seconds
+    pub time: f64,
+    // "event" or "navigation"
+    pub cue_point_type: Option<String>,
+    pub parameters: HashMap<String, AMFData>,
+}
+
+// An onTextData or onCaption script tag, a timed text sample
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct TextData {
+    pub text: String,
+    pub track_id: Option<f64>,
+    pub language: Option<String>,
+    // properties not listed above
+    pub extra: HashMap<String, AMFData>,
+}
+
+// An onFI script tag, the wall clock time and timecode of a frame written by live encoders
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct FrameInfo {
+    // "dd-mm-yyyy"
+    pub system_date: Option<String>,
+    // "hh:mm:ss.sss"
+    pub system_time: Option<String>,
+    // SMPTE, "hh:mm:ss:ff"
+    pub timecode: Option<String>,
 }
 
 #[allow(dead_code)]
@@ -23,6 +84,7 @@ pub(crate) const AMF_DATA_TYPE_NUMBER: u8 = 0;
 pub(crate) const AMF_DATA_TYPE_BOOL: u8 = 1;
 pub(crate) const AMF_DATA_TYPE_STRING: u8 = 2;
 pub(crate) const AMF_DATA_TYPE_OBJECT: u8 = 3;
+pub(crate) const AMF_DATA_TYPE_MOVIECLIP: u8 = 4;
 pub(crate) const AMF_DATA_TYPE_NULL: u8 = 5;
 pub(crate) const AMF_DATA_TYPE_UNDEFINED: u8 = 6;
 pub(crate) const AMF_DATA_TYPE_REFERENCE: u8 = 7;
@@ -32,13 +94,19 @@ pub(crate) const AMF_DATA_TYPE_ARRAY: u8 = 10;
 pub(crate) const AMF_DATA_TYPE_DATE: u8 = 11;
 pub(crate) const AMF_DATA_TYPE_LONG_STRING: u8 = 12;
 pub(crate) const AMF_DATA_TYPE_UNSUPPORTED: u8 = 13;
+pub(crate) const AMF_DATA_TYPE_RECORDSET: u8 = 14;
+pub(crate) const AMF_DATA_TYPE_XML_DOCUMENT: u8 = 15;
+pub(crate) const AMF_DATA_TYPE_TYPED_OBJECT: u8 = 16;
+pub(crate) const AMF_DATA_TYPE_AVMPLUS: u8 = 17;
 
-#[derive(Debug,PartialEq)]
+#[derive(Debug, PartialEq, Clone)]
 pub enum AMFData {
     Number(f64),
     Bool(bool),
     String(String),
     Object(Vec<AMFObject>),
+    // reserved by the spec, a marker without a value
+    MovieClip,
     Null,
     Undefined,
     Reference(u16),
@@ -47,124 +115,202 @@ pub enum AMFData {
     Array(Vec<AMFData>),
     Date(AMFDate),
     LongString(String),
+    // a marker without a value
     Unsupported,
+    // reserved by the spec, a marker without a value
+    RecordSet,
+    // an XML document, written as a long string
+    XmlDocument(String),
+    TypedObject(AMFTypedObject),
+    // the value following an AVM+ switch marker
+    AvmPlus(Amf3Value),
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct ScriptData {
+    pub name: String,
+    pub values: Vec<AMFData>,
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Clone)]
 pub struct AMFObject {
     pub name: String,
     pub data: AMFData,
 }
 
-#[derive(Debug, PartialEq)]
+// An object registered with a class name, written by the `registerClassAlias` of ActionScript
+#[derive(Debug, PartialEq, Clone)]
+pub struct AMFTypedObject {
+    pub class_name: String,
+    pub properties: Vec<AMFObject>,
+}
+
+#[derive(Debug, PartialEq, Clone)]
 pub struct AMFDate {
     pub milliseconds: f64,
     pub timezone: i16,
 }
 
-pub fn amf_data_value(input: &[u8]) -> Result<(AMFData, &[u8]), String> {
-    match input[0] {
+// Objects and arrays nested deeper than this are rejected rather than overflowing the stack
+pub const AMF_MAX_DEPTH: usize = 64;
+
+// Reads the AMF3 value following an AVM+ switch marker, then returns it with the bytes after it.
+// Errors are reported at their offset from the start of `input`.
+pub type Amf3Decoder = fn(&[u8]) -> Result<(AMFData, &[u8]), Error>;
+
+// The default `Amf3Decoder`: each switched value has reference tables of its own
+pub fn avm_plus_value(input: &[u8]) -> Result<(AMFData, &[u8]), Error> {
+    let (value, last) = amf3::amf3_value(input)?;
+    Ok((AMFData::AvmPlus(value), last))
+}
+
+// Errors are reported at their offset from the start of `input`, as for every parser below
+pub fn amf_data_value(input: &[u8]) -> Result<(AMFData, &[u8]), Error> {
+    data_value(input, 0, avm_plus_value)
+}
+
+// Reads the values following an AVM+ switch marker with `amf3`, wherever they are nested
+pub fn amf_data_value_with_amf3(input: &[u8], amf3: Amf3Decoder) -> Result<(AMFData, &[u8]), Error> {
+    data_value(input, 0, amf3)
+}
+
+fn data_value(input: &[u8], depth: usize, amf3: Amf3Decoder) -> Result<(AMFData, &[u8]), Error> {
+    if depth > AMF_MAX_DEPTH {
+        return Err(Error::NestingTooDeep { offset: 0 });
+    }
+    let (marker, last) = take(input, 1)?;
+    // everything after the marker reports offsets from the byte following it
+    match marker[0] {
         AMF_DATA_TYPE_NUMBER => {
+            let (number, last) = take(last, 8).map_err(|e| e.shift(1))?;
             Ok((AMFData::Number(f64::from_be_bytes([
-                input[1],
-                input[2],
-                input[3],
-                input[4],
-                input[5],
-                input[6],
-                input[7],
-                input[8],
-            ])), &input[9..]))
+                number[0],
+                number[1],
+                number[2],
+                number[3],
+                number[4],
+                number[5],
+                number[6],
+                number[7],
+            ])), last))
         },
         AMF_DATA_TYPE_BOOL => {
-            Ok((AMFData::Bool(input[1] != 0), &input[2..]))
+            let (b, last) = take(last, 1).map_err(|e| e.shift(1))?;
+            Ok((AMFData::Bool(b[0] != 0), last))
         },
         AMF_DATA_TYPE_STRING => {
-            let (res, last) = amf_string(&input[1..])?;
+            let (res, last) = amf_string(last).map_err(|e| e.shift(1))?;
             Ok((AMFData::String(res), last))
         },
         AMF_DATA_TYPE_OBJECT => {
-            let (res, last) = amf_objects(&input[1..])?;
+            let (res, last) = objects(last, depth + 1, amf3).map_err(|e| e.shift(1))?;
             Ok((AMFData::Object(res), last))
         }, // 3
-        AMF_DATA_TYPE_NULL => Ok((AMFData::Null, &input[1..])), // 5
-        AMF_DATA_TYPE_UNDEFINED => Ok((AMFData::Undefined, &input[1..])), // 6
+        AMF_DATA_TYPE_MOVIECLIP => Ok((AMFData::MovieClip, last)), // 4
+        AMF_DATA_TYPE_NULL => Ok((AMFData::Null, last)), // 5
+        AMF_DATA_TYPE_UNDEFINED => Ok((AMFData::Undefined, last)), // 6
         AMF_DATA_TYPE_REFERENCE => {
-            Ok((AMFData::Reference(u16::from_be_bytes([input[1], input[2]])), &input[3..]))
+            let (reference, last) = take(last, 2).map_err(|e| e.shift(1))?;
+            Ok((AMFData::Reference(u16::from_be_bytes([reference[0], reference[1]])), last))
         },
         AMF_DATA_TYPE_MIXEDARRAY => {
-            let (res, last) = amf_ecma_array(&input[1..])?;
+            let (res, last) = ecma_array(last, depth + 1, amf3).map_err(|e| e.shift(1))?;
             Ok((AMFData::Mixedarray(res), last))
         }, // 8
-        AMF_DATA_TYPE_OBJECT_END => Ok((AMFData::ObjectEnd, &input[1..])), // 9
+        AMF_DATA_TYPE_OBJECT_END => Ok((AMFData::ObjectEnd, last)), // 9
         AMF_DATA_TYPE_ARRAY => {
-            let (res, last) = amf_strict_array(&input[1..])?;
+            let (res, last) = strict_array(last, depth + 1, amf3).map_err(|e| e.shift(1))?;
             Ok((AMFData::Array(res), last))
         }, // 10
         AMF_DATA_TYPE_DATE => {
-            let (date, last) = amf_date(&input[1..]);
+            let (date, last) = amf_date(last).map_err(|e| e.shift(1))?;
             Ok((AMFData::Date(date), last))
         }, // 11
         AMF_DATA_TYPE_LONG_STRING => {
-            let (res, last) = amf_long_string(&input[1..])?;
+            let (res, last) = amf_long_string(last).map_err(|e| e.shift(1))?;
             Ok((AMFData::LongString(res), last))
         },
-        AMF_DATA_TYPE_UNSUPPORTED => Err("unsupported".to_string()),
-        other => Err(format!("reserved: {}", other).to_string())
+        AMF_DATA_TYPE_UNSUPPORTED => Ok((AMFData::Unsupported, last)), // 13
+        AMF_DATA_TYPE_RECORDSET => Ok((AMFData::RecordSet, last)), // 14
+        AMF_DATA_TYPE_XML_DOCUMENT => {
+            let (res, last) = amf_long_string(last).map_err(|e| e.shift(1))?;
+            Ok((AMFData::XmlDocument(res), last))
+        }, // 15
+        AMF_DATA_TYPE_TYPED_OBJECT => {
+            let (res, last) = typed_object(last, depth + 1, amf3).map_err(|e| e.shift(1))?;
+            Ok((AMFData::TypedObject(res), last))
+        }, // 16
+        AMF_DATA_TYPE_AVMPLUS => amf3(last).map_err(|e| e.shift(1)), // 17
+        other => Err(Error::InvalidAmfMarker { marker: other, offset: 0 }),
     }
 }
 
-pub fn amf_string(input: &[u8]) -> Result<(String, &[u8]), String> {
-    let string_length = u16::from_be_bytes([input[0], input[1]]);
-    let string_data = String::from_utf8(Vec::from(&input[2..(2+string_length) as usize]))
-        .map_err(|e| e.to_string())?;
-    Ok((string_data, &input[(2+string_length) as usize..]))
+pub fn amf_string(input: &[u8]) -> Result<(String, &[u8]), Error> {
+    let (string_length, last) = take(input, 2)?;
+    let string_length = u16::from_be_bytes([string_length[0], string_length[1]]) as usize;
+    let (string_data, last) = take(last, string_length).map_err(|e| e.shift(2))?;
+    let string_data = String::from_utf8(Vec::from(string_data))
+        .map_err(|e| Error::Utf8 { source: e.utf8_error(), offset: 2 })?;
+    Ok((string_data, last))
 }
 
-pub fn amf_long_string(input: &[u8]) -> Result<(String, &[u8]), String> {
-    let string_length = u32::from_be_bytes([input[0], input[1], input[2], input[3]]);
-    let string_data = String::from_utf8(Vec::from(&input[4..(4 + string_length) as usize]))
-        .map_err(|e| e.to_string())?;
-    Ok((string_data, &input[(4 + string_length) as usize..]))
+pub fn amf_long_string(input: &[u8]) -> Result<(String, &[u8]), Error> {
+    let (string_length, last) = take(input, 4)?;
+    let string_length = u32::from_be_bytes([
+        string_length[0],
+        string_length[1],
+        string_length[2],
+        string_length[3],
+    ]) as usize;
+    let (string_data, last) = take(last, string_length).map_err(|e| e.shift(4))?;
+    let string_data = String::from_utf8(Vec::from(string_data))
+        .map_err(|e| Error::Utf8 { source: e.utf8_error(), offset: 4 })?;
+    Ok((string_data, last))
 }
 
-pub fn amf_date(input: &[u8]) -> (AMFDate, &[u8]) {
+pub fn amf_date(input: &[u8]) -> Result<(AMFDate, &[u8]), Error> {
+    let (date, last) = take(input, 10)?;
     let milliseconds = f64::from_be_bytes([
-        input[0],
-        input[1],
-        input[2],
-        input[3],
-        input[4],
-        input[5],
-        input[6],
-        input[7],
+        date[0],
+        date[1],
+        date[2],
+        date[3],
+        date[4],
+        date[5],
+        date[6],
+        date[7],
     ]);
-    let timezone = i16::from_be_bytes([input[8],input[9]]);
-    (AMFDate{
+    let timezone = i16::from_be_bytes([date[8], date[9]]);
+    Ok((AMFDate{
         milliseconds,
         timezone,
-    }, &input[10..])
+    }, last))
 }
 
-pub fn amf_object(input: &[u8]) -> Result<(AMFObject, &[u8]), String> {
+pub fn amf_object(input: &[u8]) -> Result<(AMFObject, &[u8]), Error> {
+    object(input, 0, avm_plus_value)
+}
+
+fn object(input: &[u8], depth: usize, amf3: Amf3Decoder) -> Result<(AMFObject, &[u8]), Error> {
     let (name, last) = amf_string(input)?;
-    let (data, last) = amf_data_value(last)?;
+    let (data, last) = data_value(last, depth, amf3).map_err(|e| e.shift(consumed(input, last)))?;
     Ok((AMFObject{
         name,
         data,
     }, last))
 }
 
-pub fn amf_objects(input: &[u8]) -> Result<(Vec<AMFObject>, &[u8]), String> {
+pub fn amf_objects(input: &[u8]) -> Result<(Vec<AMFObject>, &[u8]), Error> {
+    objects(input, 0, avm_plus_value)
+}
+
+fn objects(input: &[u8], depth: usize, amf3: Amf3Decoder) -> Result<(Vec<AMFObject>, &[u8]), Error> {
     let mut res: Vec<AMFObject> = Vec::new();
     let mut last = input;
-    let mut obj = AMFObject{
-        name: "".to_string(),
-        data: AMFData::Null,
-    };
 
     loop {
-        (obj, last) = amf_object(last)?;
+        let obj;
+        (obj, last) = object(last, depth, amf3).map_err(|e| e.shift(consumed(input, last)))?;
         if obj.data != AMFData::ObjectEnd {
             res.push(obj);
         } else {
@@ -173,33 +319,49 @@ pub fn amf_objects(input: &[u8]) -> Result<(Vec<AMFObject>, &[u8]), String> {
     }
 }
 
-pub fn amf_ecma_array(input: &[u8]) -> Result<(Vec<AMFObject>, &[u8]), String> {
-    let _arr_len = u32::from_be_bytes([
-        input[0],
-        input[1],
-        input[2],
-        input[3],
-    ]);
+pub fn amf_ecma_array(input: &[u8]) -> Result<(Vec<AMFObject>, &[u8]), Error> {
+    ecma_array(input, 0, avm_plus_value)
+}
+
+fn ecma_array(input: &[u8], depth: usize, amf3: Amf3Decoder) -> Result<(Vec<AMFObject>, &[u8]), Error> {
+    let (_arr_len, last) = take(input, 4)?;
     // println!("ecma array length: {}", arr_len);
-    Ok(amf_objects(&input[4..])?)
+    objects(last, depth, amf3).map_err(|e| e.shift(4))
+}
+
+pub fn amf_typed_object(input: &[u8]) -> Result<(AMFTypedObject, &[u8]), Error> {
+    typed_object(input, 0, avm_plus_value)
 }
 
-pub fn amf_strict_array(input: &[u8]) -> Result<(Vec<AMFData>, &[u8]), String> {
+fn typed_object(input: &[u8], depth: usize, amf3: Amf3Decoder) -> Result<(AMFTypedObject, &[u8]), Error> {
+    let (class_name, last) = amf_string(input)?;
+    let (properties, last) = objects(last, depth, amf3).map_err(|e| e.shift(consumed(input, last)))?;
+    Ok((AMFTypedObject {
+        class_name,
+        properties,
+    }, last))
+}
+
+pub fn amf_strict_array(input: &[u8]) -> Result<(Vec<AMFData>, &[u8]), Error> {
+    strict_array(input, 0, avm_plus_value)
+}
+
+fn strict_array(input: &[u8], depth: usize, amf3: Amf3Decoder) -> Result<(Vec<AMFData>, &[u8]), Error> {
+    let (arr_len, mut last) = take(input, 4)?;
     let arr_len = u32::from_be_bytes([
-        input[0],
-        input[1],
-        input[2],
-        input[3],
+        arr_len[0],
+        arr_len[1],
+        arr_len[2],
+        arr_len[3],
     ]);
     // println!("strict array length: {}", arr_len);
 
     let mut idx = 0;
-    let mut last = &input[4..];
-    let mut data = AMFData::Null;
     let mut res: Vec<AMFData> = Vec::new();
 
     while idx < arr_len {
-        (data, last) = amf_data_value(last)?;
+        let data;
+        (data, last) = data_value(last, depth, amf3).map_err(|e| e.shift(consumed(input, last)))?;
         res.push(data);
         idx += 1;
     }
@@ -207,13 +369,353 @@ pub fn amf_strict_array(input: &[u8]) -> Result<(Vec<AMFData>, &[u8]), String> {
     Ok((res, last))
 }
 
-// TODO: I don't get the spec.
-// This should be a SCRIPTDATAOBJECT, but before the data, still need the [2] as string marker.
-pub fn amf_data(input: &[u8]) -> Result<(AMFObject, &[u8]), String> {
-    if input[0] == AMF_DATA_TYPE_STRING {
-        Ok(amf_object(&input[1..])?)
+// The name of a script tag and its first value, as a string marker, a string and any value.
+// `script_data` reads the whole tag body.
+pub fn amf_data(input: &[u8]) -> Result<(AMFObject, &[u8]), Error> {
+    let (marker, last) = take(input, 1)?;
+    if marker[0] == AMF_DATA_TYPE_STRING {
+        amf_object(last).map_err(|e| e.shift(1))
     } else {
-        Err("invalid data type".to_string())
+        Err(Error::InvalidAmfMarker { marker: marker[0], offset: 0 })
     }
 }
 
+// The encoders below append to `out` and are the reverse of the parsers above.
+// Strings longer than 65535 bytes are written as long strings.
+// Values read with another `Amf3Decoder` are written as the AMF0 values it returned.
+pub fn write_amf_data_value(out: &mut Vec<u8>, value: &AMFData) -> Result<(), Error> {
+    match value {
+        AMFData::Number(n) => {
+            out.push(AMF_DATA_TYPE_NUMBER);
+            out.extend_from_slice(&n.to_be_bytes());
+        },
+        AMFData::Bool(b) => {
+            out.push(AMF_DATA_TYPE_BOOL);
+            out.push(*b as u8);
+        },
+        AMFData::String(s) | AMFData::LongString(s) => {
+            if s.len() > u16::MAX as usize || matches!(value, AMFData::LongString(_)) {
+                out.push(AMF_DATA_TYPE_LONG_STRING);
+                write_amf_long_string(out, s)?;
+            } else {
+                out.push(AMF_DATA_TYPE_STRING);
+                write_amf_string(out, s)?;
+            }
+        },
+        AMFData::Object(objects) => {
+            out.push(AMF_DATA_TYPE_OBJECT);
+            write_amf_objects(out, objects)?;
+        },
+        AMFData::MovieClip => out.push(AMF_DATA_TYPE_MOVIECLIP),
+        AMFData::Null => out.push(AMF_DATA_TYPE_NULL),
+        AMFData::Undefined => out.push(AMF_DATA_TYPE_UNDEFINED),
+        AMFData::Reference(r) => {
+            out.push(AMF_DATA_TYPE_REFERENCE);
+            out.extend_from_slice(&r.to_be_bytes());
+        },
+        AMFData::Mixedarray(objects) => {
+            out.push(AMF_DATA_TYPE_MIXEDARRAY);
+            write_amf_ecma_array(out, objects)?;
+        },
+        AMFData::ObjectEnd => out.push(AMF_DATA_TYPE_OBJECT_END),
+        AMFData::Array(values) => {
+            out.push(AMF_DATA_TYPE_ARRAY);
+            write_amf_strict_array(out, values)?;
+        },
+        AMFData::Date(date) => {
+            out.push(AMF_DATA_TYPE_DATE);
+            write_amf_date(out, date);
+        },
+        AMFData::Unsupported => out.push(AMF_DATA_TYPE_UNSUPPORTED),
+        AMFData::RecordSet => out.push(AMF_DATA_TYPE_RECORDSET),
+        AMFData::XmlDocument(xml) => {
+            out.push(AMF_DATA_TYPE_XML_DOCUMENT);
+            write_amf_long_string(out, xml)?;
+        },
+        AMFData::TypedObject(object) => {
+            out.push(AMF_DATA_TYPE_TYPED_OBJECT);
+            write_amf_typed_object(out, object)?;
+        },
+        AMFData::AvmPlus(value) => {
+            out.push(AMF_DATA_TYPE_AVMPLUS);
+            amf3::write_amf3_value(out, value)?;
+        },
+    }
+    Ok(())
+}
+
+pub fn write_amf_string(out: &mut Vec<u8>, s: &str) -> Result<(), Error> {
+    let len = u16::try_from(s.len()).map_err(|_| Error::TooLarge { what: "AMF string", size: s.len() })?;
+    out.extend_from_slice(&len.to_be_bytes());
+    out.extend_from_slice(s.as_bytes());
+    Ok(())
+}
+
+pub fn write_amf_long_string(out: &mut Vec<u8>, s: &str) -> Result<(), Error> {
+    let len = u32::try_from(s.len()).map_err(|_| Error::TooLarge { what: "AMF long string", size: s.len() })?;
+    out.extend_from_slice(&len.to_be_bytes());
+    out.extend_from_slice(s.as_bytes());
+    Ok(())
+}
+
+pub fn write_amf_date(out: &mut Vec<u8>, date: &AMFDate) {
+    out.extend_from_slice(&date.milliseconds.to_be_bytes());
+    out.extend_from_slice(&date.timezone.to_be_bytes());
+}
+
+pub fn write_amf_object(out: &mut Vec<u8>, object: &AMFObject) -> Result<(), Error> {
+    write_amf_string(out, &object.name)?;
+    write_amf_data_value(out, &object.data)
+}
+
+// Writes the properties, then the empty name and object end marker closing them
+pub fn write_amf_objects(out: &mut Vec<u8>, objects: &[AMFObject]) -> Result<(), Error> {
+    for object in objects {
+        write_amf_object(out, object)?;
+    }
+    out.extend_from_slice(&[0, 0, AMF_DATA_TYPE_OBJECT_END]);
+    Ok(())
+}
+
+pub fn write_amf_ecma_array(out: &mut Vec<u8>, objects: &[AMFObject]) -> Result<(), Error> {
+    let len = u32::try_from(objects.len()).map_err(|_| Error::TooLarge { what: "AMF ECMA array", size: objects.len() })?;
+    out.extend_from_slice(&len.to_be_bytes());
+    write_amf_objects(out, objects)
+}
+
+pub fn write_amf_typed_object(out: &mut Vec<u8>, object: &AMFTypedObject) -> Result<(), Error> {
+    write_amf_string(out, &object.class_name)?;
+    write_amf_objects(out, &object.properties)
+}
+
+pub fn write_amf_strict_array(out: &mut Vec<u8>, values: &[AMFData]) -> Result<(), Error> {
+    let len = u32::try_from(values.len()).map_err(|_| Error::TooLarge { what: "AMF strict array", size: values.len() })?;
+    out.extend_from_slice(&len.to_be_bytes());
+    for value in values {
+        write_amf_data_value(out, value)?;
+    }
+    Ok(())
+}
+
+// The counterpart of `amf_data`: a SCRIPTDATA tag body
+pub fn write_amf_data(out: &mut Vec<u8>, object: &AMFObject) -> Result<(), Error> {
+    out.push(AMF_DATA_TYPE_STRING);
+    write_amf_object(out, object)
+}
+
+// The body of a script tag: a name, then every value up to the end of the tag.
+// onMetaData has a single value, `@setDataFrame` is followed by the name of the frame and its value.
+pub fn script_data(input: &[u8]) -> Result<ScriptData, Error> {
+    let (marker, last) = take(input, 1)?;
+    if marker[0] != AMF_DATA_TYPE_STRING {
+        return Err(Error::InvalidAmfMarker { marker: marker[0], offset: 0 });
+    }
+    let (name, mut last) = amf_string(last).map_err(|e| e.shift(1))?;
+    let mut values = Vec::new();
+    while !last.is_empty() {
+        let value;
+        (value, last) = amf_data_value(last).map_err(|e| e.shift(consumed(input, last)))?;
+        values.push(value);
+    }
+    Ok(ScriptData {
+        name,
+        values,
+    })
+}
+
+pub fn write_script_data(out: &mut Vec<u8>, data: &ScriptData) -> Result<(), Error> {
+    out.push(AMF_DATA_TYPE_STRING);
+    write_amf_string(out, &data.name)?;
+    for value in &data.values {
+        write_amf_data_value(out, value)?;
+    }
+    Ok(())
+}
+
+// The typed readers below return None when the name does not match,
+// or when the first value is not an object or ECMA array holding the required properties.
+impl ScriptData {
+    pub fn meta_data(&self) -> Option<MetaData> {
+        if self.name != "onMetaData" {
+            return None;
+        }
+        meta_data_properties(properties(self.values.first()?)?)
+    }
+
+    pub fn cue_point(&self) -> Option<CuePoint> {
+        if self.name != "onCuePoint" {
+            return None;
+        }
+        let mut name = None;
+        let mut time = None;
+        let mut cue_point_type = None;
+        let mut parameters = HashMap::new();
+        for property in properties(self.values.first()?)? {
+            match property.name.as_str() {
+                "name" => name = string(&property.data),
+                "time" => time = number(&property.data),
+                "type" => cue_point_type = string(&property.data),
+                "parameters" => {
+                    for parameter in properties(&property.data)? {
+                        parameters.insert(parameter.name.clone(), parameter.data.clone());
+                    }
+                },
+                _ => {},
+            }
+        }
+        Some(CuePoint {
+            name: name?,
+            time: time?,
+            cue_point_type,
+            parameters,
+        })
+    }
+
+    pub fn text_data(&self) -> Option<TextData> {
+        if self.name != "onTextData" {
+            return None;
+        }
+        text_data(properties(self.values.first()?)?)
+    }
+
+    // Captions carry the same properties as onTextData
+    pub fn caption(&self) -> Option<TextData> {
+        if self.name != "onCaption" {
+            return None;
+        }
+        text_data(properties(self.values.first()?)?)
+    }
+
+    pub fn frame_info(&self) -> Option<FrameInfo> {
+        if self.name != "onFI" {
+            return None;
+        }
+        let mut info = FrameInfo::default();
+        for property in properties(self.values.first()?)? {
+            match property.name.as_str() {
+                "sd" => info.system_date = string(&property.data),
+                "st" => info.system_time = string(&property.data),
+                "tc" => info.timecode = string(&property.data),
+                _ => {},
+            }
+        }
+        Some(info)
+    }
+}
+
+fn text_data(properties: &[AMFObject]) -> Option<TextData> {
+    let mut text = None;
+    let mut res = TextData::default();
+    for property in properties {
+        let data = &property.data;
+        let known = match property.name.as_str() {
+            "text" => set(&mut text, string(data)),
+            "trackid" => set(&mut res.track_id, number(data)),
+            "language" | "lang" => set(&mut res.language, string(data)),
+            _ => false,
+        };
+        if !known {
+            res.extra.insert(property.name.clone(), data.clone());
+        }
+    }
+    res.text = text?;
+    Some(res)
+}
+
+// Reads an onMetaData script tag, whether its properties are in an ECMA array or an object.
+// Returns None for any other script tag.
+pub fn meta_data(object: &AMFObject) -> Option<MetaData> {
+    if object.name != "onMetaData" {
+        return None;
+    }
+    meta_data_properties(properties(&object.data)?)
+}
+
+fn meta_data_properties(properties: &[AMFObject]) -> Option<MetaData> {
+    let mut meta = MetaData::default();
+    for property in properties {
+        let data = &property.data;
+        let known = match property.name.as_str() {
+            "duration" => set(&mut meta.duration, number(data)),
+            "width" => set(&mut meta.width, number(data)),
+            "height" => set(&mut meta.height, number(data)),
+            "videodatarate" => set(&mut meta.video_data_rate, number(data)),
+            "framerate" => set(&mut meta.framerate, number(data)),
+            "videocodecid" => set(&mut meta.video_codec_id, number(data)),
+            "audiosamplerate" => set(&mut meta.audio_sample_rate, number(data)),
+            "audiosamplesize" => set(&mut meta.audio_sample_size, number(data)),
+            "stereo" => set(&mut meta.stereo, boolean(data)),
+            "audiocodecid" => set(&mut meta.audio_codec_id, number(data)),
+            "filesize" => set(&mut meta.file_size, number(data)),
+            "audiodatarate" => set(&mut meta.audio_data_rate, number(data)),
+            "audiodelay" => set(&mut meta.audio_delay, number(data)),
+            "canSeekToEnd" => set(&mut meta.can_seek_to_end, boolean(data)),
+            "creationdate" => set(&mut meta.creation_date, string(data)),
+            "encoder" => set(&mut meta.encoder, string(data)),
+            "lasttimestamp" => set(&mut meta.last_timestamp, number(data)),
+            "keyframes" => set(&mut meta.keyframes, keyframes(data)),
+            _ => false,
+        };
+        if !known {
+            meta.extra.insert(property.name.clone(), data.clone());
+        }
+    }
+    Some(meta)
+}
+
+fn set<T>(field: &mut Option<T>, value: Option<T>) -> bool {
+    let ok = value.is_some();
+    if ok {
+        *field = value;
+    }
+    ok
+}
+
+fn number(data: &AMFData) -> Option<f64> {
+    match data {
+        AMFData::Number(n) => Some(*n),
+        _ => None,
+    }
+}
+
+// Some encoders write flags as numbers
+fn boolean(data: &AMFData) -> Option<bool> {
+    match data {
+        AMFData::Bool(b) => Some(*b),
+        AMFData::Number(n) => Some(*n != 0.0),
+        _ => None,
+    }
+}
+
+fn string(data: &AMFData) -> Option<String> {
+    match data {
+        AMFData::String(s) | AMFData::LongString(s) => Some(s.clone()),
+        _ => None,
+    }
+}
+
+fn properties(data: &AMFData) -> Option<&[AMFObject]> {
+    match data {
+        AMFData::Object(properties) | AMFData::Mixedarray(properties) => Some(properties),
+        _ => None,
+    }
+}
+
+fn numbers(data: &AMFData) -> Option<Vec<f64>> {
+    match data {
+        AMFData::Array(values) => values.iter().map(number).collect(),
+        _ => None,
+    }
+}
+
+fn keyframes(data: &AMFData) -> Option<KeyFrames> {
+    let mut res = KeyFrames::default();
+    for property in properties(data)? {
+        match property.name.as_str() {
+            "times" => res.times = numbers(&property.data)?,
+            "filepositions" => res.file_positions = numbers(&property.data)?,
+            _ => {},
+        }
+    }
+    Some(res)
+}
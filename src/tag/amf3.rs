@@ -0,0 +1,756 @@
+use std::collections::HashMap;
+
+use crate::error::{consumed, take};
+use crate::tag::amf0::AMF_MAX_DEPTH;
+use crate::Error;
+
+pub(crate) const AMF3_UNDEFINED: u8 = 0x00;
+pub(crate) const AMF3_NULL: u8 = 0x01;
+pub(crate) const AMF3_FALSE: u8 = 0x02;
+pub(crate) const AMF3_TRUE: u8 = 0x03;
+pub(crate) const AMF3_INTEGER: u8 = 0x04;
+pub(crate) const AMF3_DOUBLE: u8 = 0x05;
+pub(crate) const AMF3_STRING: u8 = 0x06;
+pub(crate) const AMF3_XML_DOC: u8 = 0x07;
+pub(crate) const AMF3_DATE: u8 = 0x08;
+pub(crate) const AMF3_ARRAY: u8 = 0x09;
+pub(crate) const AMF3_OBJECT: u8 = 0x0a;
+pub(crate) const AMF3_XML: u8 = 0x0b;
+pub(crate) const AMF3_BYTE_ARRAY: u8 = 0x0c;
+pub(crate) const AMF3_VECTOR_INT: u8 = 0x0d;
+pub(crate) const AMF3_VECTOR_UINT: u8 = 0x0e;
+pub(crate) const AMF3_VECTOR_DOUBLE: u8 = 0x0f;
+pub(crate) const AMF3_VECTOR_OBJECT: u8 = 0x10;
+pub(crate) const AMF3_DICTIONARY: u8 = 0x11;
+
+// Integers outside of this range are written as doubles
+pub const AMF3_INTEGER_MIN: i32 = -(1 << 28);
+pub const AMF3_INTEGER_MAX: i32 = (1 << 28) - 1;
+
+// References are resolved into copies of the values they point to: a few bytes of references
+// can stand for a huge tree, so decoding stops once a value holds this many values in total,
+// or this many bytes of strings, XML, byte arrays and vectors.
+// The copies kept in the reference tables count as well.
+pub const AMF3_MAX_VALUES: usize = 1 << 20;
+pub const AMF3_MAX_BYTES: usize = 1 << 24;
+
+// A value of the AMF3 format (AMF 3 Specification 3).
+// References to strings, traits and objects are resolved while decoding.
+// The encoder writes references to the strings, traits, dates, XML and byte arrays written before.
+#[derive(Debug, PartialEq, Clone)]
+pub enum Amf3Value {
+    Undefined,
+    Null,
+    Bool(bool),
+    // [bit;29], signed
+    Integer(i32),
+    Double(f64),
+    String(String),
+    // the legacy flash.xml.XMLDocument
+    XmlDocument(String),
+    // milliseconds since the epoch, UTC
+    Date(f64),
+    Array(Amf3Array),
+    Object(Amf3Object),
+    // E4X XML
+    Xml(String),
+    ByteArray(Vec<u8>),
+    VectorInt(Amf3Vector<i32>),
+    VectorUint(Amf3Vector<u32>),
+    VectorDouble(Amf3Vector<f64>),
+    VectorObject(Amf3ObjectVector),
+    Dictionary(Amf3Dictionary),
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Amf3Member {
+    pub name: String,
+    pub value: Amf3Value,
+}
+
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct Amf3Array {
+    // the string keys, in order, each one non-empty
+    pub associative: Vec<Amf3Member>,
+    // the values at index 0 to n - 1
+    pub dense: Vec<Amf3Value>,
+}
+
+// The class of an object, sent once then referenced by the following objects of the same class
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Default)]
+pub struct Amf3Traits {
+    // empty for an anonymous object
+    pub class_name: String,
+    pub dynamic: bool,
+    // the object writes its own content, see `ExternalizableReader`
+    pub externalizable: bool,
+    pub sealed_names: Vec<String>,
+}
+
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct Amf3Object {
+    pub traits: Amf3Traits,
+    // one per sealed name of the traits
+    pub sealed_values: Vec<Amf3Value>,
+    // only for dynamic traits, each name non-empty
+    pub dynamic_members: Vec<Amf3Member>,
+    // only for externalizable traits, what the `ExternalizableReader` returned
+    pub external: Option<Box<Amf3Value>>,
+}
+
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct Amf3Vector<T> {
+    pub fixed: bool,
+    pub values: Vec<T>,
+}
+
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct Amf3ObjectVector {
+    pub fixed: bool,
+    // "*" for any type
+    pub type_name: String,
+    pub values: Vec<Amf3Value>,
+}
+
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct Amf3Dictionary {
+    pub weak_keys: bool,
+    pub entries: Vec<(Amf3Value, Amf3Value)>,
+}
+
+// Reads the content of an externalizable object of the class `class_name`, which only the class knows the layout of.
+// Returns it with the bytes after it, with error offsets from the start of `input`.
+pub type ExternalizableReader = for<'a> fn(&mut Amf3Reader, &str, &'a [u8]) -> Result<(Amf3Value, &'a [u8]), Error>;
+
+// The counterpart of `ExternalizableReader`: writes the content of `object`
+pub type ExternalizableWriter = fn(&mut Amf3Writer, &mut Vec<u8>, &Amf3Object) -> Result<(), Error>;
+
+// The Flex collections write the value they wrap, sharing the reference tables of the message
+const FLEX_WRAPPERS: [&str; 3] = [
+    "flex.messaging.io.ArrayCollection",
+    "flex.messaging.io.ArrayList",
+    "flex.messaging.io.ObjectProxy",
+];
+
+// The default `ExternalizableReader`, which knows the Flex collections only
+pub fn read_flex_externalizable<'a>(
+    reader: &mut Amf3Reader,
+    class_name: &str,
+    input: &'a [u8],
+) -> Result<(Amf3Value, &'a [u8]), Error> {
+    if !FLEX_WRAPPERS.contains(&class_name) {
+        return Err(Error::Unsupported { what: "AMF3 externalizable class", offset: 0 });
+    }
+    reader.value(input)
+}
+
+// The default `ExternalizableWriter`, writing the content as a value
+pub fn write_flex_externalizable(writer: &mut Amf3Writer, out: &mut Vec<u8>, object: &Amf3Object) -> Result<(), Error> {
+    match &object.external {
+        Some(value) => writer.write_value(out, value),
+        None => Ok(()),
+    }
+}
+
+// Decodes values sharing the same reference tables, like the arguments of a Flex message.
+// Errors are reported at their offset from the start of the input of each call.
+pub struct Amf3Reader {
+    strings: Vec<String>,
+    // None while the value is being decoded
+    objects: Vec<Option<(Amf3Value, Size)>>,
+    traits: Vec<Amf3Traits>,
+    externalizable: ExternalizableReader,
+    depth: usize,
+    // what the top level value being decoded holds, counting the copies made for references
+    decoded: Size,
+}
+
+// The number of values and bytes a value holds, charged again for every copy of it
+#[derive(Debug, Clone, Copy, Default)]
+struct Size {
+    values: usize,
+    bytes: usize,
+}
+
+impl Default for Amf3Reader {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Amf3Reader {
+    pub fn new() -> Self {
+        Amf3Reader {
+            strings: Vec::new(),
+            objects: Vec::new(),
+            traits: Vec::new(),
+            externalizable: read_flex_externalizable,
+            depth: 0,
+            decoded: Size::default(),
+        }
+    }
+
+    // Only the Flex collections are read by default
+    pub fn set_externalizable_reader(&mut self, reader: ExternalizableReader) {
+        self.externalizable = reader;
+    }
+
+    pub fn value<'a>(&mut self, input: &'a [u8]) -> Result<(Amf3Value, &'a [u8]), Error> {
+        if self.depth > AMF_MAX_DEPTH {
+            return Err(Error::NestingTooDeep { offset: 0 });
+        }
+        if self.depth == 0 {
+            self.decoded = Size::default();
+        }
+        self.depth += 1;
+        let res = self.marker_value(input);
+        self.depth -= 1;
+        res
+    }
+
+    fn count(&mut self, size: Size) -> Result<(), Error> {
+        self.decoded.values += size.values;
+        self.decoded.bytes += size.bytes;
+        if self.decoded.values > AMF3_MAX_VALUES {
+            return Err(Error::Unsupported { what: "AMF3 value expanding past AMF3_MAX_VALUES", offset: 0 });
+        }
+        if self.decoded.bytes > AMF3_MAX_BYTES {
+            return Err(Error::Unsupported { what: "AMF3 value expanding past AMF3_MAX_BYTES", offset: 0 });
+        }
+        Ok(())
+    }
+
+    fn count_bytes(&mut self, bytes: usize) -> Result<(), Error> {
+        self.count(Size { values: 0, bytes })
+    }
+
+    fn marker_value<'a>(&mut self, input: &'a [u8]) -> Result<(Amf3Value, &'a [u8]), Error> {
+        let (marker, last) = take(input, 1)?;
+        self.count(Size { values: 1, bytes: 0 })?;
+        // everything after the marker reports offsets from the byte following it
+        let (value, last) = match marker[0] {
+            AMF3_UNDEFINED => (Amf3Value::Undefined, last),
+            AMF3_NULL => (Amf3Value::Null, last),
+            AMF3_FALSE => (Amf3Value::Bool(false), last),
+            AMF3_TRUE => (Amf3Value::Bool(true), last),
+            AMF3_INTEGER => {
+                let (n, last) = amf3_u29(last).map_err(|e| e.shift(1))?;
+                // sign extends the 29 bits
+                (Amf3Value::Integer(((n << 3) as i32) >> 3), last)
+            },
+            AMF3_DOUBLE => {
+                let (n, last) = double(last).map_err(|e| e.shift(1))?;
+                (Amf3Value::Double(n), last)
+            },
+            AMF3_STRING => {
+                let (s, last) = self.string(last).map_err(|e| e.shift(1))?;
+                (Amf3Value::String(s), last)
+            },
+            AMF3_XML_DOC | AMF3_DATE | AMF3_ARRAY | AMF3_OBJECT | AMF3_XML | AMF3_BYTE_ARRAY
+            | AMF3_VECTOR_INT | AMF3_VECTOR_UINT | AMF3_VECTOR_DOUBLE | AMF3_VECTOR_OBJECT | AMF3_DICTIONARY => {
+                self.referenced(marker[0], last).map_err(|e| e.shift(1))?
+            },
+            other => return Err(Error::InvalidAmfMarker { marker: other, offset: 0 }),
+        };
+        Ok((value, last))
+    }
+
+    // UTF-8-vr: a string, or a reference to one of the strings read before
+    fn string<'a>(&mut self, input: &'a [u8]) -> Result<(String, &'a [u8]), Error> {
+        let (header, last) = amf3_u29(input)?;
+        if header & 1 == 0 {
+            let index = (header >> 1) as usize;
+            let len = self.strings.get(index).ok_or(Error::InvalidAmfReference { index, offset: 0 })?.len();
+            self.count_bytes(len)?;
+            return Ok((self.strings[index].clone(), last));
+        }
+        let (s, last) = utf8(last, header >> 1).map_err(|e| e.shift(consumed(input, last)))?;
+        self.count_bytes(s.len())?;
+        // the empty string is never sent by reference
+        if !s.is_empty() {
+            self.strings.push(s.clone());
+        }
+        Ok((s, last))
+    }
+
+    // The values added to the object reference table, starting with a reference or an inline header
+    fn referenced<'a>(&mut self, marker: u8, input: &'a [u8]) -> Result<(Amf3Value, &'a [u8]), Error> {
+        let (header, last) = amf3_u29(input)?;
+        if header & 1 == 0 {
+            let index = (header >> 1) as usize;
+            let size = match self.objects.get(index) {
+                Some(Some((_, size))) => *size,
+                Some(None) => return Err(Error::Unsupported { what: "AMF3 cyclic reference", offset: 0 }),
+                None => return Err(Error::InvalidAmfReference { index, offset: 0 }),
+            };
+            // charged before the copy is made
+            self.count(size)?;
+            let Some((value, _)) = &self.objects[index] else { unreachable!() };
+            return Ok((value.clone(), last));
+        }
+
+        // the value is added to the table before its members, which may reference it
+        let index = self.objects.len();
+        self.objects.push(None);
+        let start = self.decoded;
+        let header = header >> 1;
+        let (value, last) = match marker {
+            AMF3_XML_DOC => {
+                let (s, last) = utf8(last, header).map_err(|e| e.shift(consumed(input, last)))?;
+                self.count_bytes(s.len())?;
+                (Amf3Value::XmlDocument(s), last)
+            },
+            AMF3_XML => {
+                let (s, last) = utf8(last, header).map_err(|e| e.shift(consumed(input, last)))?;
+                self.count_bytes(s.len())?;
+                (Amf3Value::Xml(s), last)
+            },
+            AMF3_DATE => {
+                let (n, last) = double(last).map_err(|e| e.shift(consumed(input, last)))?;
+                (Amf3Value::Date(n), last)
+            },
+            AMF3_BYTE_ARRAY => {
+                let (bytes, last) = take(last, header as usize).map_err(|e| e.shift(consumed(input, last)))?;
+                self.count_bytes(bytes.len())?;
+                (Amf3Value::ByteArray(Vec::from(bytes)), last)
+            },
+            AMF3_ARRAY => self.array(input, header, last)?,
+            AMF3_OBJECT => self.object(input, header, last)?,
+            AMF3_VECTOR_INT => {
+                let (fixed, values, last) = vector(input, header, last, i32::from_be_bytes)?;
+                self.count_bytes(values.len() * 4)?;
+                (Amf3Value::VectorInt(Amf3Vector { fixed, values }), last)
+            },
+            AMF3_VECTOR_UINT => {
+                let (fixed, values, last) = vector(input, header, last, u32::from_be_bytes)?;
+                self.count_bytes(values.len() * 4)?;
+                (Amf3Value::VectorUint(Amf3Vector { fixed, values }), last)
+            },
+            AMF3_VECTOR_DOUBLE => {
+                let (fixed, values, last) = vector(input, header, last, f64::from_be_bytes)?;
+                self.count_bytes(values.len() * 8)?;
+                (Amf3Value::VectorDouble(Amf3Vector { fixed, values }), last)
+            },
+            AMF3_VECTOR_OBJECT => self.object_vector(input, header, last)?,
+            _ => self.dictionary(input, header, last)?,
+        };
+        let size = Size {
+            // and the marker, counted before this call
+            values: self.decoded.values - start.values + 1,
+            bytes: self.decoded.bytes - start.bytes,
+        };
+        // the copy kept in the table
+        self.count(size)?;
+        self.objects[index] = Some((value.clone(), size));
+        Ok((value, last))
+    }
+
+    // Reads values until the empty name closing them
+    fn members<'a>(
+        &mut self,
+        input: &'a [u8],
+        mut last: &'a [u8],
+    ) -> Result<(Vec<Amf3Member>, &'a [u8]), Error> {
+        let mut res = Vec::new();
+        loop {
+            let name;
+            (name, last) = self.string(last).map_err(|e| e.shift(consumed(input, last)))?;
+            if name.is_empty() {
+                return Ok((res, last));
+            }
+            let value;
+            (value, last) = self.value(last).map_err(|e| e.shift(consumed(input, last)))?;
+            res.push(Amf3Member { name, value });
+        }
+    }
+
+    // Reads `count` values
+    fn values<'a>(
+        &mut self,
+        input: &'a [u8],
+        count: u32,
+        mut last: &'a [u8],
+    ) -> Result<(Vec<Amf3Value>, &'a [u8]), Error> {
+        let mut res = Vec::new();
+        for _ in 0..count {
+            let value;
+            (value, last) = self.value(last).map_err(|e| e.shift(consumed(input, last)))?;
+            res.push(value);
+        }
+        Ok((res, last))
+    }
+
+    fn array<'a>(&mut self, input: &'a [u8], dense_count: u32, last: &'a [u8]) -> Result<(Amf3Value, &'a [u8]), Error> {
+        let (associative, last) = self.members(input, last)?;
+        let (dense, last) = self.values(input, dense_count, last)?;
+        Ok((Amf3Value::Array(Amf3Array { associative, dense }), last))
+    }
+
+    fn object<'a>(&mut self, input: &'a [u8], header: u32, mut last: &'a [u8]) -> Result<(Amf3Value, &'a [u8]), Error> {
+        let traits = if header & 1 == 0 {
+            let index = (header >> 1) as usize;
+            let traits = self.traits.get(index).ok_or(Error::InvalidAmfReference { index, offset: 0 })?;
+            let len = traits.class_name.len() + traits.sealed_names.iter().map(String::len).sum::<usize>();
+            self.count_bytes(len)?;
+            self.traits[index].clone()
+        } else {
+            let class_name;
+            (class_name, last) = self.string(last).map_err(|e| e.shift(consumed(input, last)))?;
+            let mut sealed_names = Vec::new();
+            for _ in 0..header >> 3 {
+                let name;
+                (name, last) = self.string(last).map_err(|e| e.shift(consumed(input, last)))?;
+                sealed_names.push(name);
+            }
+            let traits = Amf3Traits {
+                class_name,
+                dynamic: header & 0b100 != 0,
+                externalizable: header & 0b10 != 0,
+                sealed_names,
+            };
+            self.traits.push(traits.clone());
+            traits
+        };
+
+        let mut object = Amf3Object::default();
+        if traits.externalizable {
+            let external;
+            (external, last) = (self.externalizable)(self, &traits.class_name, last)
+                .map_err(|e| e.shift(consumed(input, last)))?;
+            object.external = Some(Box::new(external));
+        } else {
+            (object.sealed_values, last) = self.values(input, traits.sealed_names.len() as u32, last)?;
+            if traits.dynamic {
+                (object.dynamic_members, last) = self.members(input, last)?;
+            }
+        }
+        object.traits = traits;
+        Ok((Amf3Value::Object(object), last))
+    }
+
+    fn object_vector<'a>(&mut self, input: &'a [u8], count: u32, last: &'a [u8]) -> Result<(Amf3Value, &'a [u8]), Error> {
+        let (fixed, last) = take(last, 1).map_err(|e| e.shift(consumed(input, last)))?;
+        let (type_name, last) = self.string(last).map_err(|e| e.shift(consumed(input, last)))?;
+        let (values, last) = self.values(input, count, last)?;
+        Ok((Amf3Value::VectorObject(Amf3ObjectVector {
+            fixed: fixed[0] != 0,
+            type_name,
+            values,
+        }), last))
+    }
+
+    fn dictionary<'a>(&mut self, input: &'a [u8], count: u32, last: &'a [u8]) -> Result<(Amf3Value, &'a [u8]), Error> {
+        let (weak_keys, mut last) = take(last, 1).map_err(|e| e.shift(consumed(input, last)))?;
+        let mut entries = Vec::new();
+        for _ in 0..count {
+            let key;
+            (key, last) = self.value(last).map_err(|e| e.shift(consumed(input, last)))?;
+            let value;
+            (value, last) = self.value(last).map_err(|e| e.shift(consumed(input, last)))?;
+            entries.push((key, value));
+        }
+        Ok((Amf3Value::Dictionary(Amf3Dictionary {
+            weak_keys: weak_keys[0] != 0,
+            entries,
+        }), last))
+    }
+}
+
+// Decodes a value with reference tables of its own, like the value following an AVM+ switch in AMF0
+pub fn amf3_value(input: &[u8]) -> Result<(Amf3Value, &[u8]), Error> {
+    Amf3Reader::new().value(input)
+}
+
+// U29: 7 bits in each of the first 3 bytes while their high bit is set, then 8 bits in the 4th
+pub fn amf3_u29(input: &[u8]) -> Result<(u32, &[u8]), Error> {
+    let mut n = 0;
+    for (i, &b) in input.iter().enumerate().take(4) {
+        if i == 3 {
+            return Ok((n << 8 | b as u32, &input[4..]));
+        }
+        n = n << 7 | (b & 0x7f) as u32;
+        if b & 0x80 == 0 {
+            return Ok((n, &input[i + 1..]));
+        }
+    }
+    Err(Error::Truncated { needed: input.len() + 1, offset: 0 })
+}
+
+fn double(input: &[u8]) -> Result<(f64, &[u8]), Error> {
+    let (n, last) = take(input, 8)?;
+    Ok((f64::from_be_bytes([n[0], n[1], n[2], n[3], n[4], n[5], n[6], n[7]]), last))
+}
+
+fn utf8(input: &[u8], len: u32) -> Result<(String, &[u8]), Error> {
+    let (s, last) = take(input, len as usize)?;
+    let s = String::from_utf8(Vec::from(s)).map_err(|e| Error::Utf8 { source: e.utf8_error(), offset: 0 })?;
+    Ok((s, last))
+}
+
+// The fixed flag, then `count` numbers of `N` bytes
+fn vector<'a, T, const N: usize>(
+    input: &'a [u8],
+    count: u32,
+    last: &'a [u8],
+    from_be_bytes: fn([u8; N]) -> T,
+) -> Result<(bool, Vec<T>, &'a [u8]), Error> {
+    let (fixed, last) = take(last, 1).map_err(|e| e.shift(consumed(input, last)))?;
+    let (items, last) = take(last, count as usize * N).map_err(|e| e.shift(consumed(input, last)))?;
+    let values = items
+        .chunks_exact(N)
+        .map(|item| from_be_bytes(item.try_into().unwrap()))
+        .collect();
+    Ok((fixed[0] != 0, values, last))
+}
+
+// Encodes values sharing the same reference tables.
+// Strings, traits, dates, XML and byte arrays equal to one written before are written as references to it.
+// Arrays, objects, vectors and dictionaries are always written in full: the decoded values do not tell
+// which ones were the same object, so one referenced twice when decoded is written twice.
+pub struct Amf3Writer {
+    strings: HashMap<String, usize>,
+    traits: HashMap<Amf3Traits, usize>,
+    // number of values written in full that a reader adds to its object table
+    objects: usize,
+    // the marker and content of the dates, XML and byte arrays among them
+    object_refs: HashMap<(u8, Vec<u8>), usize>,
+    externalizable: ExternalizableWriter,
+}
+
+impl Default for Amf3Writer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// U29 holds at most 29 bits, less the flags written next to lengths and references
+const U28_MAX: usize = (1 << 28) - 1;
+
+impl Amf3Writer {
+    pub fn new() -> Self {
+        Amf3Writer {
+            strings: HashMap::new(),
+            traits: HashMap::new(),
+            objects: 0,
+            object_refs: HashMap::new(),
+            externalizable: write_flex_externalizable,
+        }
+    }
+
+    // The content of externalizable objects is written as a value by default
+    pub fn set_externalizable_writer(&mut self, writer: ExternalizableWriter) {
+        self.externalizable = writer;
+    }
+
+    pub fn write_value(&mut self, out: &mut Vec<u8>, value: &Amf3Value) -> Result<(), Error> {
+        // they take their index in the object table before their members
+        if matches!(value, Amf3Value::Array(_) | Amf3Value::Object(_) | Amf3Value::VectorInt(_)
+            | Amf3Value::VectorUint(_) | Amf3Value::VectorDouble(_) | Amf3Value::VectorObject(_)
+            | Amf3Value::Dictionary(_))
+        {
+            self.objects += 1;
+        }
+        match value {
+            Amf3Value::Undefined => out.push(AMF3_UNDEFINED),
+            Amf3Value::Null => out.push(AMF3_NULL),
+            Amf3Value::Bool(false) => out.push(AMF3_FALSE),
+            Amf3Value::Bool(true) => out.push(AMF3_TRUE),
+            Amf3Value::Integer(n) if (AMF3_INTEGER_MIN..=AMF3_INTEGER_MAX).contains(n) => {
+                out.push(AMF3_INTEGER);
+                write_amf3_u29(out, *n as u32 & 0x1fff_ffff);
+            },
+            Amf3Value::Integer(n) => {
+                out.push(AMF3_DOUBLE);
+                out.extend_from_slice(&(*n as f64).to_be_bytes());
+            },
+            Amf3Value::Double(n) => {
+                out.push(AMF3_DOUBLE);
+                out.extend_from_slice(&n.to_be_bytes());
+            },
+            Amf3Value::String(s) => {
+                out.push(AMF3_STRING);
+                self.write_string(out, s)?;
+            },
+            Amf3Value::XmlDocument(s) => {
+                if !self.write_reference(out, AMF3_XML_DOC, s.as_bytes()) {
+                    write_inline(out, "AMF3 XML document", s.len())?;
+                    out.extend_from_slice(s.as_bytes());
+                }
+            },
+            Amf3Value::Date(n) => {
+                if !self.write_reference(out, AMF3_DATE, &n.to_be_bytes()) {
+                    write_amf3_u29(out, 1);
+                    out.extend_from_slice(&n.to_be_bytes());
+                }
+            },
+            Amf3Value::Array(array) => {
+                out.push(AMF3_ARRAY);
+                write_inline(out, "AMF3 array", array.dense.len())?;
+                self.write_members(out, &array.associative)?;
+                for value in &array.dense {
+                    self.write_value(out, value)?;
+                }
+            },
+            Amf3Value::Object(object) => {
+                out.push(AMF3_OBJECT);
+                self.write_object(out, object)?;
+            },
+            Amf3Value::Xml(s) => {
+                if !self.write_reference(out, AMF3_XML, s.as_bytes()) {
+                    write_inline(out, "AMF3 XML", s.len())?;
+                    out.extend_from_slice(s.as_bytes());
+                }
+            },
+            Amf3Value::ByteArray(bytes) => {
+                if !self.write_reference(out, AMF3_BYTE_ARRAY, bytes) {
+                    write_inline(out, "AMF3 byte array", bytes.len())?;
+                    out.extend_from_slice(bytes);
+                }
+            },
+            Amf3Value::VectorInt(vector) => {
+                out.push(AMF3_VECTOR_INT);
+                write_inline(out, "AMF3 vector", vector.values.len())?;
+                out.push(vector.fixed as u8);
+                for n in &vector.values {
+                    out.extend_from_slice(&n.to_be_bytes());
+                }
+            },
+            Amf3Value::VectorUint(vector) => {
+                out.push(AMF3_VECTOR_UINT);
+                write_inline(out, "AMF3 vector", vector.values.len())?;
+                out.push(vector.fixed as u8);
+                for n in &vector.values {
+                    out.extend_from_slice(&n.to_be_bytes());
+                }
+            },
+            Amf3Value::VectorDouble(vector) => {
+                out.push(AMF3_VECTOR_DOUBLE);
+                write_inline(out, "AMF3 vector", vector.values.len())?;
+                out.push(vector.fixed as u8);
+                for n in &vector.values {
+                    out.extend_from_slice(&n.to_be_bytes());
+                }
+            },
+            Amf3Value::VectorObject(vector) => {
+                out.push(AMF3_VECTOR_OBJECT);
+                write_inline(out, "AMF3 vector", vector.values.len())?;
+                out.push(vector.fixed as u8);
+                self.write_string(out, &vector.type_name)?;
+                for value in &vector.values {
+                    self.write_value(out, value)?;
+                }
+            },
+            Amf3Value::Dictionary(dictionary) => {
+                out.push(AMF3_DICTIONARY);
+                write_inline(out, "AMF3 dictionary", dictionary.entries.len())?;
+                out.push(dictionary.weak_keys as u8);
+                for (key, value) in &dictionary.entries {
+                    self.write_value(out, key)?;
+                    self.write_value(out, value)?;
+                }
+            },
+        }
+        Ok(())
+    }
+
+    // Writes the marker, then a reference if an equal value was written before.
+    // Otherwise the value takes the next index of the object table, and the caller writes it in full.
+    fn write_reference(&mut self, out: &mut Vec<u8>, marker: u8, content: &[u8]) -> bool {
+        out.push(marker);
+        let key = (marker, Vec::from(content));
+        if let Some(&index) = self.object_refs.get(&key) {
+            write_amf3_u29(out, (index as u32) << 1);
+            return true;
+        }
+        if self.objects <= U28_MAX {
+            self.object_refs.insert(key, self.objects);
+        }
+        self.objects += 1;
+        false
+    }
+
+    fn write_string(&mut self, out: &mut Vec<u8>, s: &str) -> Result<(), Error> {
+        if let Some(&index) = self.strings.get(s) {
+            write_amf3_u29(out, (index as u32) << 1);
+            return Ok(());
+        }
+        write_inline(out, "AMF3 string", s.len())?;
+        out.extend_from_slice(s.as_bytes());
+        if !s.is_empty() && self.strings.len() <= U28_MAX {
+            self.strings.insert(s.to_string(), self.strings.len());
+        }
+        Ok(())
+    }
+
+    // Writes the members, then the empty name closing them
+    fn write_members(&mut self, out: &mut Vec<u8>, members: &[Amf3Member]) -> Result<(), Error> {
+        for member in members {
+            self.write_string(out, &member.name)?;
+            self.write_value(out, &member.value)?;
+        }
+        self.write_string(out, "")
+    }
+
+    fn write_object(&mut self, out: &mut Vec<u8>, object: &Amf3Object) -> Result<(), Error> {
+        let traits = &object.traits;
+        match self.traits.get(traits) {
+            Some(&index) => write_amf3_u29(out, (index as u32) << 2 | 0b01),
+            None => {
+                let count = traits.sealed_names.len();
+                if count >= 1 << 25 {
+                    return Err(Error::TooLarge { what: "AMF3 traits", size: count });
+                }
+                write_amf3_u29(out, (count as u32) << 4
+                    | (traits.dynamic as u32) << 3
+                    | (traits.externalizable as u32) << 2
+                    | 0b11);
+                self.write_string(out, &traits.class_name)?;
+                for name in &traits.sealed_names {
+                    self.write_string(out, name)?;
+                }
+                if self.traits.len() < 1 << 27 {
+                    self.traits.insert(traits.clone(), self.traits.len());
+                }
+            },
+        }
+
+        if traits.externalizable {
+            return (self.externalizable)(self, out, object);
+        }
+        // missing sealed values are written as undefined
+        for i in 0..traits.sealed_names.len() {
+            self.write_value(out, object.sealed_values.get(i).unwrap_or(&Amf3Value::Undefined))?;
+        }
+        if traits.dynamic {
+            self.write_members(out, &object.dynamic_members)?;
+        }
+        Ok(())
+    }
+}
+
+// The counterpart of `amf3_value`
+pub fn write_amf3_value(out: &mut Vec<u8>, value: &Amf3Value) -> Result<(), Error> {
+    Amf3Writer::new().write_value(out, value)
+}
+
+// `n` must hold in 29 bits
+pub fn write_amf3_u29(out: &mut Vec<u8>, n: u32) {
+    if n < 1 << 7 {
+        out.push(n as u8);
+    } else if n < 1 << 14 {
+        out.extend_from_slice(&[(n >> 7) as u8 | 0x80, n as u8 & 0x7f]);
+    } else if n < 1 << 21 {
+        out.extend_from_slice(&[(n >> 14) as u8 | 0x80, (n >> 7) as u8 | 0x80, n as u8 & 0x7f]);
+    } else {
+        out.extend_from_slice(&[(n >> 22) as u8 | 0x80, (n >> 15) as u8 | 0x80, (n >> 8) as u8 | 0x80, n as u8]);
+    }
+}
+
+// The header of a value written in full: its length or count, and the flag telling it is not a reference
+fn write_inline(out: &mut Vec<u8>, what: &'static str, len: usize) -> Result<(), Error> {
+    if len > U28_MAX {
+        return Err(Error::TooLarge { what, size: len });
+    }
+    write_amf3_u29(out, (len as u32) << 1 | 1);
+    Ok(())
+}
@@ -1,3 +1,12 @@
+pub mod aac;
+pub mod adpcm;
+pub mod flac;
+pub mod mp3;
+pub mod opus;
+
+use crate::error::take;
+use crate::Error;
+
 
 // [bit;4]
 #[derive(Debug, PartialEq)]
@@ -9,11 +18,17 @@ pub enum SoundFormat {
     Nellymoser16KHZMono,
     Nellymoser8KHZMono,
     Nellymoser,
+    G711ALaw,   // G.711 A-law, 8 kHz
+    G711MuLaw,  // G.711 mu-law, 8 kHz
     AAC,
     Speex,
-    Reserved,
-    InternalUse,
-    Invalid,
+    MP38KHZ,    // MP3 at 8 kHz
+    DeviceSpecific,
+    // 9, Enhanced FLV: the codec is given by a FourCC,
+    // and the other 4 bits of the header are the AudioPacketType
+    ExHeader,
+    // 12 or 13, reserved
+    Invalid(u8),
 }
 
 // [bit;2]
@@ -25,6 +40,18 @@ pub enum SoundRate {
     _44KHZ,  // 44khz, aac always this
 }
 
+impl SoundRate {
+    // 5.5 kHz is really 5512.5 Hz, rounded down
+    pub fn hz(&self) -> u32 {
+        match self {
+            SoundRate::_5_5KHZ => 5512,
+            SoundRate::_11KHZ => 11025,
+            SoundRate::_22KHZ => 22050,
+            SoundRate::_44KHZ => 44100,
+        }
+    }
+}
+
 // [bit;1]
 #[derive(Debug, PartialEq)]
 pub enum SoundSize {
@@ -52,9 +79,12 @@ pub fn audio_header(b: u8) -> (SoundFormat, SoundRate, SoundSize, SoundType) {
         6 => SoundFormat::Nellymoser,
         10 => SoundFormat::AAC,
         11 => SoundFormat::Speex,
-        7 | 8 | 14 | 15 => SoundFormat::InternalUse,
-        9 => SoundFormat::Reserved,
-        _ => SoundFormat::Invalid,
+        7 => SoundFormat::G711ALaw,
+        8 => SoundFormat::G711MuLaw,
+        14 => SoundFormat::MP38KHZ,
+        15 => SoundFormat::DeviceSpecific,
+        9 => SoundFormat::ExHeader,
+        other => SoundFormat::Invalid(other),
     };
     let rate = (b >> 2) & 0b11;
     let rate = match rate {
@@ -83,44 +113,396 @@ pub fn audio_header(b: u8) -> (SoundFormat, SoundRate, SoundSize, SoundType) {
 #[derive(Debug, PartialEq)]
 pub struct AudioData {
     pub sound_format: SoundFormat,
+    // for Enhanced FLV, the bits of the AudioPacketType, and ignored by the writer
     pub sound_rate: SoundRate,
     pub sound_size: SoundSize,
     pub sound_type: SoundType,
-    // if sound_format == 10, AACAudioData
-    // else varies by format
-    pub sound_data: Vec<u8>,
+    pub sound_data: SoundData,
+}
+
+impl AudioData {
+    // The sample rate in Hz, for the formats whose SoundRate does not apply.
+    // AAC, MP3 and Enhanced FLV streams carry their own, see `AACAudioData::audio_specific_config`,
+    // `mp3_frame_header` and `ExAudioPacket`.
+    pub fn sample_rate(&self) -> u32 {
+        match self.sound_format {
+            SoundFormat::Nellymoser16KHZMono => 16000,
+            SoundFormat::Nellymoser8KHZMono
+            | SoundFormat::G711ALaw
+            | SoundFormat::G711MuLaw
+            | SoundFormat::MP38KHZ => 8000,
+            // Speex is always 16 kHz
+            SoundFormat::Speex => 16000,
+            _ => self.sound_rate.hz(),
+        }
+    }
+
+    pub fn channels(&self) -> u8 {
+        match (&self.sound_format, &self.sound_type) {
+            (SoundFormat::Nellymoser16KHZMono | SoundFormat::Nellymoser8KHZMono | SoundFormat::Speex, _) => 1,
+            (_, SoundType::Mono) => 1,
+            (_, SoundType::Stereo) => 2,
+        }
+    }
+
+    // Parses the header of the first MP3 frame, which gives the real sample rate and channels.
+    // None for other formats.
+    pub fn mp3_frame_header(&self) -> Option<Result<mp3::MpegAudioFrameHeader, Error>> {
+        match (&self.sound_format, &self.sound_data) {
+            (SoundFormat::MP3 | SoundFormat::MP38KHZ, SoundData::Raw(data)) => Some(mp3::mpeg_audio_frame_header(data)),
+            _ => None,
+        }
+    }
 }
 
-pub fn audio_data(input: &[u8], size: usize) -> Result<AudioData, String> {
+#[derive(Debug, PartialEq)]
+pub enum SoundData {
+    // sound_format == 10
+    AAC(AACAudioData),
+    // sound_format == 9
+    Ex(ExAudioPacket),
+    // every other format, varies by format
+    Raw(Vec<u8>),
+}
+
+pub fn audio_data(input: &[u8], size: usize) -> Result<AudioData, Error> {
+    if input.len() < size {
+        return Err(Error::Truncated { needed: size, offset: 0 });
+    }
+    if size < 1 {
+        return Err(Error::Truncated { needed: 1, offset: 0 });
+    }
     let (sound_format, sound_rate, sound_size, sound_type) =
         audio_header(input[0]);
+    let sound_data = match sound_format {
+        SoundFormat::ExHeader => SoundData::Ex(ex_audio_packet(&input[..size])?),
+        SoundFormat::AAC => {
+            SoundData::AAC(aac_audio_packet(&input[1..size], size - 1).map_err(|e| e.shift(1))?)
+        },
+        _ => SoundData::Raw(Vec::from(&input[1..size])),
+    };
 
     Ok(AudioData{
         sound_format,
         sound_rate,
         sound_size,
         sound_type,
-        sound_data: Vec::from(&input[1..size]),
+        sound_data,
     })
 }
 
+#[derive(Debug, PartialEq, Clone)]
+pub enum AACPacketType {
+    // 0
+    SequenceHeader,
+    // 1
+    Raw,
+}
+
+// The SoundRate and SoundType of AAC audio are always 44 kHz and stereo:
+// the real values are in the AudioSpecificConfig.
+#[derive(Debug, PartialEq, Clone)]
 pub struct AACAudioData {
-    // 0: AAC sequence header
-    // 1: AAC raw
-    pub aac_packet_type: u8,
+    pub aac_packet_type: AACPacketType,
     // if type == 0, AudioSpecificConfig
     // else 1: AAC frame data
     // The AudioSpecificConfig is explained in ISO 14496-3
     pub data: Vec<u8>,
 }
 
-pub fn aac_audio_packet(input: &[u8], size: usize) -> Result<AACAudioData, String> {
+impl AACAudioData {
+    // Parses the data of a sequence header packet, None for raw frames
+    pub fn audio_specific_config(&self) -> Option<Result<aac::AudioSpecificConfig, Error>> {
+        match self.aac_packet_type {
+            AACPacketType::SequenceHeader => Some(aac::audio_specific_config(&self.data)),
+            AACPacketType::Raw => None,
+        }
+    }
+}
+
+pub fn aac_audio_packet(input: &[u8], size: usize) -> Result<AACAudioData, Error> {
     if input.len() < size {
-        return Err("aac audio packet need more size".to_string());
+        return Err(Error::Truncated { needed: size, offset: 0 });
     }
-    let aac_packet_type = input[0];
+    if size < 1 {
+        return Err(Error::Truncated { needed: 1, offset: 0 });
+    }
+    let aac_packet_type = aac_packet_type(input[0])?;
     Ok(AACAudioData{
         aac_packet_type,
         data: Vec::from(&input[1..size]),
     })
 }
+
+fn aac_packet_type(input: u8) -> Result<AACPacketType, Error> {
+    match input {
+        0 => Ok(AACPacketType::SequenceHeader),
+        1 => Ok(AACPacketType::Raw),
+        _ => Err(Error::InvalidAacPacketType { packet_type: input, offset: 0 }),
+    }
+}
+
+fn write_aac_packet_type(packet_type: &AACPacketType) -> u8 {
+    match packet_type {
+        AACPacketType::SequenceHeader => 0,
+        AACPacketType::Raw => 1,
+    }
+}
+
+// AudioTagHeader, the part of the audio data left in the clear in an encrypted tag
+#[derive(Debug, PartialEq)]
+pub struct AudioTagHeader {
+    pub sound_format: SoundFormat,
+    pub sound_rate: SoundRate,
+    pub sound_size: SoundSize,
+    pub sound_type: SoundType,
+    // only for AAC
+    pub aac_packet_type: Option<AACPacketType>,
+}
+
+pub fn audio_tag_header(input: &[u8]) -> Result<(AudioTagHeader, &[u8]), Error> {
+    let (b, last) = take(input, 1)?;
+    let (sound_format, sound_rate, sound_size, sound_type) = audio_header(b[0]);
+    let (aac_packet_type, last) = match sound_format {
+        SoundFormat::AAC => {
+            let (b, last) = take(last, 1).map_err(|e| e.shift(1))?;
+            (Some(aac_packet_type(b[0]).map_err(|e| e.shift(1))?), last)
+        },
+        _ => (None, last),
+    };
+    Ok((AudioTagHeader {
+        sound_format,
+        sound_rate,
+        sound_size,
+        sound_type,
+        aac_packet_type,
+    }, last))
+}
+
+pub fn write_audio_tag_header(out: &mut Vec<u8>, header: &AudioTagHeader) {
+    out.push(write_audio_header(&header.sound_format, &header.sound_rate, &header.sound_size, &header.sound_type));
+    if let Some(packet_type) = &header.aac_packet_type {
+        out.push(write_aac_packet_type(packet_type));
+    }
+}
+
+// The low 4 bits of the header of Enhanced FLV audio, in place of SoundRate, SoundSize and SoundType
+#[derive(Debug, PartialEq, Clone)]
+pub enum AudioPacketType {
+    // 0, the configuration of the codec
+    SequenceStart,
+    // 1
+    CodedFrames,
+    // 2, empty
+    SequenceEnd,
+    // 4, the channel layout
+    MultichannelConfig,
+}
+
+fn audio_packet_type(input: u8) -> Result<AudioPacketType, Error> {
+    match input {
+        0 => Ok(AudioPacketType::SequenceStart),
+        1 => Ok(AudioPacketType::CodedFrames),
+        2 => Ok(AudioPacketType::SequenceEnd),
+        4 => Ok(AudioPacketType::MultichannelConfig),
+        5 => Err(Error::Unsupported { what: "multitrack audio", offset: 0 }),
+        7 => Err(Error::Unsupported { what: "audio packet modifier extension", offset: 0 }),
+        _ => Err(Error::InvalidAudioPacketType { packet_type: input, offset: 0 }),
+    }
+}
+
+// The FourCC of Enhanced FLV audio
+#[derive(Debug, PartialEq, Clone)]
+pub enum AudioCodec {
+    // 'Opus'
+    Opus,
+    // 'fLaC'
+    FLAC,
+    // 'ac-3'
+    AC3,
+    // 'ec-3'
+    EAC3,
+    // 'mp4a'
+    AAC,
+    // '.mp3'
+    MP3,
+    // a FourCC this crate does not know, its packets kept as they are
+    Unknown([u8; 4]),
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct ExAudioPacket {
+    pub packet_type: AudioPacketType,
+    pub codec: AudioCodec,
+    // if PacketType == SequenceStart, OpusHead for Opus, the metadata blocks for FLAC,
+    //   AudioSpecificConfig for AAC, empty for the others
+    // else if PacketType == CodedFrames, the frames
+    // else if PacketType == MultichannelConfig, see `MultichannelConfig`
+    // else Empty
+    pub data: Vec<u8>,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct MultichannelConfig {
+    pub channel_count: u8,
+    pub channel_order: AudioChannelOrder,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum AudioChannelOrder {
+    // 0, only the channel count is known
+    Unspecified,
+    // 1, the speakers present, one bit each in the order of AudioChannelMask
+    Native(u32),
+    // 2, the speaker of each channel, AudioChannel values
+    Custom(Vec<u8>),
+}
+
+impl ExAudioPacket {
+    // Parses the data of a sequence start packet of Opus audio, None for other packet types
+    pub fn opus_head(&self) -> Option<Result<opus::OpusHead, Error>> {
+        match self.packet_type {
+            AudioPacketType::SequenceStart => Some(opus::opus_head(&self.data)),
+            _ => None,
+        }
+    }
+
+    // Parses the data of a sequence start packet of FLAC audio, None for other packet types
+    pub fn flac_stream_info(&self) -> Option<Result<flac::FlacStreamInfo, Error>> {
+        match self.packet_type {
+            AudioPacketType::SequenceStart => Some(flac::flac_stream_info(&self.data)),
+            _ => None,
+        }
+    }
+
+    // Parses the data of a sequence start packet of AAC audio, None for other packet types
+    pub fn audio_specific_config(&self) -> Option<Result<aac::AudioSpecificConfig, Error>> {
+        match self.packet_type {
+            AudioPacketType::SequenceStart => Some(aac::audio_specific_config(&self.data)),
+            _ => None,
+        }
+    }
+
+    // Parses the data of a multichannel config packet, None for other packet types
+    pub fn multichannel_config(&self) -> Option<Result<MultichannelConfig, Error>> {
+        match self.packet_type {
+            AudioPacketType::MultichannelConfig => Some(multichannel_config(&self.data)),
+            _ => None,
+        }
+    }
+}
+
+pub fn multichannel_config(input: &[u8]) -> Result<MultichannelConfig, Error> {
+    let (fixed, last) = take(input, 2)?;
+    let channel_count = fixed[1];
+    let channel_order = match fixed[0] {
+        0 => AudioChannelOrder::Unspecified,
+        1 => {
+            let (flags, _) = take(last, 4).map_err(|e| e.shift(2))?;
+            AudioChannelOrder::Native(u32::from_be_bytes([flags[0], flags[1], flags[2], flags[3]]))
+        },
+        2 => {
+            let (mapping, _) = take(last, channel_count as usize).map_err(|e| e.shift(2))?;
+            AudioChannelOrder::Custom(Vec::from(mapping))
+        },
+        _ => return Err(Error::Unsupported { what: "audio channel order", offset: 0 }),
+    };
+    Ok(MultichannelConfig {
+        channel_count,
+        channel_order,
+    })
+}
+
+// `input` is the whole tag body, its size already checked
+fn ex_audio_packet(input: &[u8]) -> Result<ExAudioPacket, Error> {
+    let packet_type = audio_packet_type(input[0] & 0b1111)?;
+    let (four_cc, last) = take(&input[1..], 4).map_err(|e| e.shift(1))?;
+    let codec = match four_cc {
+        b"Opus" => AudioCodec::Opus,
+        b"fLaC" => AudioCodec::FLAC,
+        b"ac-3" => AudioCodec::AC3,
+        b"ec-3" => AudioCodec::EAC3,
+        b"mp4a" => AudioCodec::AAC,
+        b".mp3" => AudioCodec::MP3,
+        _ => AudioCodec::Unknown([four_cc[0], four_cc[1], four_cc[2], four_cc[3]]),
+    };
+    Ok(ExAudioPacket {
+        packet_type,
+        codec,
+        data: Vec::from(last),
+    })
+}
+
+// The reverse of `audio_header`
+pub fn write_audio_header(sound_format: &SoundFormat, sound_rate: &SoundRate, sound_size: &SoundSize, sound_type: &SoundType) -> u8 {
+    let format: u8 = match sound_format {
+        SoundFormat::LinearPCMPE => 0,
+        SoundFormat::ADPCM => 1,
+        SoundFormat::MP3 => 2,
+        SoundFormat::LinearPCMLE => 3,
+        SoundFormat::Nellymoser16KHZMono => 4,
+        SoundFormat::Nellymoser8KHZMono => 5,
+        SoundFormat::Nellymoser => 6,
+        SoundFormat::AAC => 10,
+        SoundFormat::Speex => 11,
+        SoundFormat::G711ALaw => 7,
+        SoundFormat::G711MuLaw => 8,
+        SoundFormat::MP38KHZ => 14,
+        SoundFormat::DeviceSpecific => 15,
+        SoundFormat::ExHeader => 9,
+        SoundFormat::Invalid(format) => *format & 0b1111,
+    };
+    let rate: u8 = match sound_rate {
+        SoundRate::_5_5KHZ => 0,
+        SoundRate::_11KHZ => 1,
+        SoundRate::_22KHZ => 2,
+        SoundRate::_44KHZ => 3,
+    };
+    let size: u8 = match sound_size {
+        SoundSize::_8Bit => 0,
+        SoundSize::_16Bit => 1,
+    };
+    let sound_type: u8 = match sound_type {
+        SoundType::Mono => 0,
+        SoundType::Stereo => 1,
+    };
+    format << 4 | rate << 2 | size << 1 | sound_type
+}
+
+// For Enhanced FLV audio, the SoundRate, SoundSize and SoundType are replaced by the AudioPacketType
+pub fn write_audio_data(out: &mut Vec<u8>, data: &AudioData) {
+    if let SoundData::Ex(packet) = &data.sound_data {
+        write_ex_audio_packet(out, packet);
+        return;
+    }
+    out.push(write_audio_header(&data.sound_format, &data.sound_rate, &data.sound_size, &data.sound_type));
+    match &data.sound_data {
+        SoundData::AAC(packet) => {
+            out.push(write_aac_packet_type(&packet.aac_packet_type));
+            out.extend_from_slice(&packet.data);
+        },
+        SoundData::Raw(data) => out.extend_from_slice(data),
+        SoundData::Ex(_) => unreachable!(),
+    }
+}
+
+// The header byte, FourCC and body of an Enhanced FLV audio tag
+pub fn write_ex_audio_packet(out: &mut Vec<u8>, packet: &ExAudioPacket) {
+    let packet_type: u8 = match packet.packet_type {
+        AudioPacketType::SequenceStart => 0,
+        AudioPacketType::CodedFrames => 1,
+        AudioPacketType::SequenceEnd => 2,
+        AudioPacketType::MultichannelConfig => 4,
+    };
+    out.push(9 << 4 | packet_type);
+    out.extend_from_slice(match &packet.codec {
+        AudioCodec::Opus => b"Opus",
+        AudioCodec::FLAC => b"fLaC",
+        AudioCodec::AC3 => b"ac-3",
+        AudioCodec::EAC3 => b"ec-3",
+        AudioCodec::AAC => b"mp4a",
+        AudioCodec::MP3 => b".mp3",
+        AudioCodec::Unknown(four_cc) => four_cc,
+    });
+    out.extend_from_slice(&packet.data);
+}
@@ -0,0 +1,224 @@
+use crate::bits::BitReader;
+use crate::Error;
+
+// AudioSpecificConfig, the data of an AAC sequence header (ISO 14496-3 1.6.2.1)
+#[derive(Debug, PartialEq, Clone)]
+pub struct AudioSpecificConfig {
+    // the core object type, 2 for AAC-LC, even when SBR or PS is signalled
+    pub audio_object_type: u8,
+    // [bit;4], 15 if the frequency is explicit
+    pub sampling_frequency_index: u8,
+    // Hz, of the core decoder
+    pub sampling_frequency: u32,
+    // [bit;4], 0 if the channels are described by a program config element
+    pub channel_configuration: u8,
+    // false: 1024 samples per frame, true: 960
+    pub frame_length_flag: bool,
+    // 5 for SBR (HE-AAC), 22 for ER BSAC extension
+    pub extension_audio_object_type: Option<u8>,
+    // Hz, output frequency of the SBR decoder
+    pub extension_sampling_frequency: Option<u32>,
+    pub sbr_present: bool,
+    // parametric stereo (HE-AAC v2)
+    pub ps_present: bool,
+}
+
+// Table 1.18, indexed by samplingFrequencyIndex
+const SAMPLING_FREQUENCIES: [u32; 13] = [
+    96000, 88200, 64000, 48000, 44100, 32000, 24000, 22050, 16000, 12000, 11025, 8000, 7350,
+];
+const EXPLICIT_FREQUENCY: u32 = 0xf;
+const AOT_ESCAPE: u32 = 31;
+const AOT_SBR: u8 = 5;
+const AOT_PS: u8 = 29;
+const AOT_ER_BSAC: u8 = 22;
+const SYNC_EXTENSION_SBR: u32 = 0x2b7;
+const SYNC_EXTENSION_PS: u32 = 0x548;
+pub const ADTS_HEADER_SIZE: usize = 7;
+
+impl AudioSpecificConfig {
+    // Number of output channels, None if they are described by a program config element
+    pub fn channels(&self) -> Option<u8> {
+        let channels = match self.channel_configuration {
+            0 => return None,
+            1 if self.ps_present => 2,
+            c @ 1..=6 => c,
+            7 => 8,
+            11 => 7,
+            12 | 14 => 8,
+            13 => 24,
+            _ => return None,
+        };
+        Some(channels)
+    }
+
+    // The frequency the decoded audio is played at, doubled by SBR
+    pub fn output_sampling_frequency(&self) -> u32 {
+        match self.extension_sampling_frequency {
+            Some(frequency) if self.sbr_present => frequency,
+            _ => self.sampling_frequency,
+        }
+    }
+
+    // The ADTS header (ISO 14496-3 1.A.2.2) of a raw frame of `frame_size` bytes, without CRC.
+    // ADTS only carries the object types 1 to 4 and a sampling frequency index,
+    // SBR and PS stay implicit.
+    pub fn adts_header(&self, frame_size: usize) -> Result<[u8; ADTS_HEADER_SIZE], Error> {
+        if !(1..=4).contains(&self.audio_object_type) {
+            return Err(Error::Unsupported { what: "AAC object type in ADTS", offset: 0 });
+        }
+        if self.sampling_frequency_index as u32 == EXPLICIT_FREQUENCY {
+            return Err(Error::Unsupported { what: "explicit AAC sampling frequency in ADTS", offset: 0 });
+        }
+        if self.channel_configuration > 7 {
+            return Err(Error::Unsupported { what: "AAC channel configuration in ADTS", offset: 1 });
+        }
+        // [bit;13], the header included
+        let frame_length = frame_size + ADTS_HEADER_SIZE;
+        if frame_length > 0x1fff {
+            return Err(Error::TooLarge { what: "ADTS frame", size: frame_length });
+        }
+        let profile = self.audio_object_type - 1;
+        let frame_length = frame_length as u16;
+        Ok([
+            // syncword
+            0xff,
+            // syncword, MPEG-4, layer 0, protection_absent
+            0xf1,
+            profile << 6 | self.sampling_frequency_index << 2 | self.channel_configuration >> 2,
+            (self.channel_configuration & 0b11) << 6 | (frame_length >> 11) as u8,
+            (frame_length >> 3) as u8,
+            // buffer fullness 0x7ff: variable bitrate
+            ((frame_length & 0b111) as u8) << 5 | 0b1_1111,
+            // one raw data block
+            0b1111_1100,
+        ])
+    }
+}
+
+pub fn audio_specific_config(input: &[u8]) -> Result<AudioSpecificConfig, Error> {
+    let mut r = BitReader::new(input);
+    let mut audio_object_type = audio_object_type(&mut r)?;
+    let (sampling_frequency_index, sampling_frequency) = frequency(&mut r)?;
+    let channel_configuration = r.read_bits(4)? as u8;
+
+    let mut extension_audio_object_type = None;
+    let mut extension_sampling_frequency = None;
+    let mut sbr_present = false;
+    let mut ps_present = false;
+    // implicit signalling: the object type is SBR or PS, the core object type follows
+    if audio_object_type == AOT_SBR || audio_object_type == AOT_PS {
+        extension_audio_object_type = Some(AOT_SBR);
+        sbr_present = true;
+        ps_present = audio_object_type == AOT_PS;
+        extension_sampling_frequency = Some(frequency(&mut r)?.1);
+        audio_object_type = self::audio_object_type(&mut r)?;
+        if audio_object_type == AOT_ER_BSAC {
+            // extensionChannelConfiguration
+            r.skip_bits(4)?;
+        }
+    }
+
+    let mut frame_length_flag = false;
+    // only the GASpecificConfig can be followed by explicit signalling here,
+    // and not when a program config element or error protection config comes first
+    let mut explicit_signalling = false;
+    if matches!(audio_object_type, 1..=4 | 6 | 7 | 17 | 19..=23) {
+        frame_length_flag = r.read_bit()?;
+        let depends_on_core_coder = r.read_bit()?;
+        if depends_on_core_coder {
+            // coreCoderDelay
+            r.skip_bits(14)?;
+        }
+        let extension_flag = r.read_bit()?;
+        if channel_configuration != 0 {
+            explicit_signalling = true;
+            if audio_object_type == 6 || audio_object_type == 20 {
+                // layerNr
+                r.skip_bits(3)?;
+            }
+            if extension_flag {
+                if audio_object_type == AOT_ER_BSAC {
+                    // numOfSubFrame, layer_length
+                    r.skip_bits(16)?;
+                }
+                if matches!(audio_object_type, 17 | 19 | 20 | 23) {
+                    // aacSectionDataResilienceFlag, aacScalefactorDataResilienceFlag,
+                    // aacSpectralDataResilienceFlag
+                    r.skip_bits(3)?;
+                }
+                // extensionFlag3
+                r.skip_bits(1)?;
+            }
+            if matches!(audio_object_type, 17 | 19..=23) {
+                let ep_config = r.read_bits(2)?;
+                if ep_config == 2 || ep_config == 3 {
+                    explicit_signalling = false;
+                }
+            }
+        }
+    }
+
+    // backward compatible explicit signalling, appended after the core config
+    if explicit_signalling && extension_audio_object_type.is_none() && r.bits_left() >= 16 {
+        let sync_extension_type = r.read_bits(11)?;
+        if sync_extension_type == SYNC_EXTENSION_SBR {
+            let extension_type = self::audio_object_type(&mut r)?;
+            if extension_type == AOT_SBR {
+                extension_audio_object_type = Some(extension_type);
+                sbr_present = r.read_bit()?;
+                if sbr_present {
+                    extension_sampling_frequency = Some(frequency(&mut r)?.1);
+                    if r.bits_left() >= 12 {
+                        let sync_extension_type = r.read_bits(11)?;
+                        if sync_extension_type == SYNC_EXTENSION_PS {
+                            ps_present = r.read_bit()?;
+                        }
+                    }
+                }
+            } else if extension_type == AOT_ER_BSAC {
+                extension_audio_object_type = Some(extension_type);
+                sbr_present = r.read_bit()?;
+                if sbr_present {
+                    extension_sampling_frequency = Some(frequency(&mut r)?.1);
+                }
+                // extensionChannelConfiguration
+                r.skip_bits(4)?;
+            }
+        }
+    }
+
+    Ok(AudioSpecificConfig {
+        audio_object_type,
+        sampling_frequency_index,
+        sampling_frequency,
+        channel_configuration,
+        frame_length_flag,
+        extension_audio_object_type,
+        extension_sampling_frequency,
+        sbr_present,
+        ps_present,
+    })
+}
+
+// GetAudioObjectType(), 5 bits with an escape to 6 more
+fn audio_object_type(r: &mut BitReader) -> Result<u8, Error> {
+    let object_type = r.read_bits(5)?;
+    if object_type == AOT_ESCAPE {
+        return Ok(32 + r.read_bits(6)? as u8);
+    }
+    Ok(object_type as u8)
+}
+
+// A 4 bit index into SAMPLING_FREQUENCIES, or 15 then the frequency on 24 bits
+fn frequency(r: &mut BitReader) -> Result<(u8, u32), Error> {
+    let offset = r.byte_offset();
+    let index = r.read_bits(4)?;
+    if index == EXPLICIT_FREQUENCY {
+        return Ok((index as u8, r.read_bits(24)?));
+    }
+    match SAMPLING_FREQUENCIES.get(index as usize) {
+        Some(&frequency) => Ok((index as u8, frequency)),
+        None => Err(Error::Unsupported { what: "reserved AAC sampling frequency index", offset }),
+    }
+}
@@ -0,0 +1,81 @@
+use crate::bits::BitReader;
+use crate::Error;
+
+// Samples per channel in a block, the first one is stored whole
+const BLOCK_SAMPLES: usize = 4096;
+// initial sample and step index
+const BLOCK_HEADER_BITS: usize = 16 + 6;
+
+// IMA ADPCM step sizes
+const STEPS: [i32; 89] = [
+    7, 8, 9, 10, 11, 12, 13, 14, 16, 17, 19, 21, 23, 25, 28, 31, 34, 37, 41, 45, 50, 55, 60, 66, 73, 80, 88, 97,
+    107, 118, 130, 143, 157, 173, 190, 209, 230, 253, 279, 307, 337, 371, 408, 449, 494, 544, 598, 658, 724,
+    796, 876, 963, 1060, 1166, 1282, 1411, 1552, 1707, 1878, 2066, 2272, 2499, 2749, 3024, 3327, 3660, 4026,
+    4428, 4871, 5358, 5894, 6484, 7132, 7845, 8630, 9493, 10442, 11487, 12635, 13899, 15289, 16818, 18500,
+    20350, 22385, 24623, 27086, 29794, 32767,
+];
+
+// Step index adjustments, indexed by the magnitude bits of a code
+const INDEX_2: [i32; 2] = [-1, 2];
+const INDEX_3: [i32; 4] = [-1, -1, 2, 4];
+const INDEX_4: [i32; 8] = [-1, -1, -1, -1, 2, 4, 6, 8];
+const INDEX_5: [i32; 16] = [-1, -1, -1, -1, -1, -1, -1, -1, 1, 2, 4, 6, 8, 10, 13, 16];
+
+// Decodes the SoundData of an ADPCM tag (SWF 19, ADPCMSOUNDDATA) to 16 bit PCM, channels interleaved.
+// The data starts with the code size, then blocks of 4096 samples per channel,
+// each with the first sample and step index of every channel.
+pub fn decode_adpcm(input: &[u8], channels: usize) -> Result<Vec<i16>, Error> {
+    if channels == 0 {
+        return Ok(Vec::new());
+    }
+    let mut r = BitReader::new(input);
+    let bits = r.read_bits(2)? + 2;
+    let index_table: &[i32] = match bits {
+        2 => &INDEX_2,
+        3 => &INDEX_3,
+        4 => &INDEX_4,
+        _ => &INDEX_5,
+    };
+    let sign = 1 << (bits - 1);
+
+    let mut out = Vec::new();
+    let mut predictors = vec![0i32; channels];
+    let mut indexes = vec![0i32; channels];
+    // the last block may be cut short, leftover padding bits are not a block
+    while r.bits_left() >= BLOCK_HEADER_BITS * channels {
+        for (predictor, index) in predictors.iter_mut().zip(indexes.iter_mut()) {
+            *predictor = r.read_bits(16)? as u16 as i16 as i32;
+            *index = r.read_bits(6)? as i32;
+            out.push(*predictor as i16);
+        }
+        for _ in 1..BLOCK_SAMPLES {
+            if r.bits_left() < bits as usize * channels {
+                break;
+            }
+            for (predictor, index) in predictors.iter_mut().zip(indexes.iter_mut()) {
+                let code = r.read_bits(bits)?;
+                let mut step = STEPS[*index as usize];
+                // step * (magnitude + 0.5) / 2^(bits - 2), by shifts
+                let mut difference = 0;
+                let mut bit = sign >> 1;
+                while bit > 0 {
+                    if code & bit != 0 {
+                        difference += step;
+                    }
+                    step >>= 1;
+                    bit >>= 1;
+                }
+                difference += step;
+                if code & sign != 0 {
+                    *predictor -= difference;
+                } else {
+                    *predictor += difference;
+                }
+                *predictor = (*predictor).clamp(i16::MIN as i32, i16::MAX as i32);
+                *index = (*index + index_table[(code & !sign) as usize]).clamp(0, STEPS.len() as i32 - 1);
+                out.push(*predictor as i16);
+            }
+        }
+    }
+    Ok(out)
+}
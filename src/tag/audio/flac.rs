@@ -0,0 +1,61 @@
+use crate::bits::BitReader;
+use crate::error::take;
+use crate::Error;
+
+// The STREAMINFO metadata block, the first of the data of a FLAC sequence start (RFC 9639 8.2)
+#[derive(Debug, PartialEq, Clone)]
+pub struct FlacStreamInfo {
+    // in samples
+    pub min_block_size: u16,
+    pub max_block_size: u16,
+    // in bytes, 0 if unknown
+    pub min_frame_size: u32,
+    pub max_frame_size: u32,
+    // [bit;20], Hz
+    pub sample_rate: u32,
+    // 1 to 8
+    pub channels: u8,
+    // 4 to 32
+    pub bits_per_sample: u8,
+    // [bit;36], per channel, 0 if unknown
+    pub total_samples: u64,
+    // of the decoded samples
+    pub md5: [u8; 16],
+}
+
+const STREAMINFO_SIZE: usize = 34;
+const BLOCK_TYPE_STREAMINFO: u8 = 0;
+
+// `input` is the metadata blocks, with or without the 'fLaC' marker of a FLAC file in front
+pub fn flac_stream_info(input: &[u8]) -> Result<FlacStreamInfo, Error> {
+    let start = if input.starts_with(b"fLaC") { 4 } else { 0 };
+    let (block_header, last) = take(&input[start..], 4).map_err(|e| e.shift(start))?;
+    if block_header[0] & 0x7f != BLOCK_TYPE_STREAMINFO {
+        return Err(Error::Unsupported { what: "FLAC metadata without STREAMINFO first", offset: start });
+    }
+    let (block, _) = take(last, STREAMINFO_SIZE).map_err(|e| e.shift(start + 4))?;
+
+    let mut r = BitReader::new(block);
+    let min_block_size = r.read_bits(16)? as u16;
+    let max_block_size = r.read_bits(16)? as u16;
+    let min_frame_size = r.read_bits(24)?;
+    let max_frame_size = r.read_bits(24)?;
+    let sample_rate = r.read_bits(20)?;
+    let channels = r.read_bits(3)? as u8 + 1;
+    let bits_per_sample = r.read_bits(5)? as u8 + 1;
+    let total_samples = (r.read_bits(4)? as u64) << 32 | r.read_bits(32)? as u64;
+    let mut md5 = [0; 16];
+    md5.copy_from_slice(&block[18..]);
+
+    Ok(FlacStreamInfo {
+        min_block_size,
+        max_block_size,
+        min_frame_size,
+        max_frame_size,
+        sample_rate,
+        channels,
+        bits_per_sample,
+        total_samples,
+        md5,
+    })
+}
@@ -0,0 +1,148 @@
+use crate::error::take;
+use crate::Error;
+
+pub const MPEG_AUDIO_HEADER_SIZE: usize = 4;
+
+// The header of an MPEG audio frame (ISO 11172-3 2.4.2.3, extended by ISO 13818-3 for 2 and 2.5)
+#[derive(Debug, PartialEq, Clone)]
+pub struct MpegAudioFrameHeader {
+    pub version: MpegVersion,
+    pub layer: MpegLayer,
+    // a 16 bit CRC follows the header
+    pub protected: bool,
+    // kbit/s, free format is not supported
+    pub bitrate: u32,
+    // Hz
+    pub sample_rate: u32,
+    // one more slot in the frame
+    pub padding: bool,
+    pub channel_mode: ChannelMode,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum MpegVersion {
+    // 3
+    Mpeg1,
+    // 2
+    Mpeg2,
+    // 0, unofficial extension to low sample rates
+    Mpeg25,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum MpegLayer {
+    // 3
+    Layer1,
+    // 2
+    Layer2,
+    // 1
+    Layer3,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum ChannelMode {
+    Stereo,
+    JointStereo,
+    DualChannel,
+    Mono,
+}
+
+// kbit/s, indexed by bitrate_index, 0 is free format and 15 is invalid
+const BITRATES_V1_L1: [u32; 15] = [0, 32, 64, 96, 128, 160, 192, 224, 256, 288, 320, 352, 384, 416, 448];
+const BITRATES_V1_L2: [u32; 15] = [0, 32, 48, 56, 64, 80, 96, 112, 128, 160, 192, 224, 256, 320, 384];
+const BITRATES_V1_L3: [u32; 15] = [0, 32, 40, 48, 56, 64, 80, 96, 112, 128, 160, 192, 224, 256, 320];
+const BITRATES_V2_L1: [u32; 15] = [0, 32, 48, 56, 64, 80, 96, 112, 128, 144, 160, 176, 192, 224, 256];
+const BITRATES_V2_L2_L3: [u32; 15] = [0, 8, 16, 24, 32, 40, 48, 56, 64, 80, 96, 112, 128, 144, 160];
+
+impl MpegAudioFrameHeader {
+    pub fn channels(&self) -> u8 {
+        match self.channel_mode {
+            ChannelMode::Mono => 1,
+            _ => 2,
+        }
+    }
+
+    pub fn samples_per_frame(&self) -> u32 {
+        match (&self.layer, &self.version) {
+            (MpegLayer::Layer1, _) => 384,
+            (MpegLayer::Layer3, MpegVersion::Mpeg2 | MpegVersion::Mpeg25) => 576,
+            _ => 1152,
+        }
+    }
+
+    // Size in bytes of the whole frame, header included
+    pub fn frame_size(&self) -> usize {
+        let bitrate = self.bitrate as usize * 1000;
+        let sample_rate = self.sample_rate as usize;
+        let padding = self.padding as usize;
+        match self.layer {
+            // 4 byte slots
+            MpegLayer::Layer1 => (12 * bitrate / sample_rate + padding) * 4,
+            _ => self.samples_per_frame() as usize / 8 * bitrate / sample_rate + padding,
+        }
+    }
+}
+
+pub fn mpeg_audio_frame_header(input: &[u8]) -> Result<MpegAudioFrameHeader, Error> {
+    let (header, _) = take(input, MPEG_AUDIO_HEADER_SIZE)?;
+    // 11 bit frame sync
+    if header[0] != 0xff || header[1] & 0b1110_0000 != 0b1110_0000 {
+        return Err(Error::BadSignature { what: "MPEG audio frame sync", offset: 0 });
+    }
+    let version = match header[1] >> 3 & 0b11 {
+        0 => MpegVersion::Mpeg25,
+        2 => MpegVersion::Mpeg2,
+        3 => MpegVersion::Mpeg1,
+        _ => return Err(Error::Unsupported { what: "reserved MPEG audio version", offset: 1 }),
+    };
+    let layer = match header[1] >> 1 & 0b11 {
+        1 => MpegLayer::Layer3,
+        2 => MpegLayer::Layer2,
+        3 => MpegLayer::Layer1,
+        _ => return Err(Error::Unsupported { what: "reserved MPEG audio layer", offset: 1 }),
+    };
+    let protected = header[1] & 1 == 0;
+
+    let bitrates = match (&version, &layer) {
+        (MpegVersion::Mpeg1, MpegLayer::Layer1) => &BITRATES_V1_L1,
+        (MpegVersion::Mpeg1, MpegLayer::Layer2) => &BITRATES_V1_L2,
+        (MpegVersion::Mpeg1, MpegLayer::Layer3) => &BITRATES_V1_L3,
+        (_, MpegLayer::Layer1) => &BITRATES_V2_L1,
+        _ => &BITRATES_V2_L2_L3,
+    };
+    let bitrate = match header[2] >> 4 {
+        0 => return Err(Error::Unsupported { what: "free format MPEG audio bitrate", offset: 2 }),
+        index => match bitrates.get(index as usize) {
+            Some(&bitrate) => bitrate,
+            None => return Err(Error::Unsupported { what: "invalid MPEG audio bitrate", offset: 2 }),
+        },
+    };
+    let sample_rate = match header[2] >> 2 & 0b11 {
+        3 => return Err(Error::Unsupported { what: "reserved MPEG audio sample rate", offset: 2 }),
+        index => {
+            let base = [44100, 48000, 32000][index as usize];
+            match version {
+                MpegVersion::Mpeg1 => base,
+                MpegVersion::Mpeg2 => base / 2,
+                MpegVersion::Mpeg25 => base / 4,
+            }
+        },
+    };
+    let padding = header[2] >> 1 & 1 == 1;
+    let channel_mode = match header[3] >> 6 {
+        0 => ChannelMode::Stereo,
+        1 => ChannelMode::JointStereo,
+        2 => ChannelMode::DualChannel,
+        _ => ChannelMode::Mono,
+    };
+
+    Ok(MpegAudioFrameHeader {
+        version,
+        layer,
+        protected,
+        bitrate,
+        sample_rate,
+        padding,
+        channel_mode,
+    })
+}
@@ -0,0 +1,60 @@
+use crate::error::{consumed, take};
+use crate::Error;
+
+// The identification header of an Opus stream, the data of an Opus sequence start (RFC 7845 5.1)
+#[derive(Debug, PartialEq, Clone)]
+pub struct OpusHead {
+    // 1, the major version in the high 4 bits
+    pub version: u8,
+    pub channel_count: u8,
+    // samples at 48 kHz to drop from the start of the decoded output
+    pub pre_skip: u16,
+    // Hz, of the original audio; Opus always decodes at 48 kHz
+    pub input_sample_rate: u32,
+    // Q7.8 dB
+    pub output_gain: i16,
+    // 0: mono or stereo, 1: Vorbis order, 255: no defined order
+    pub channel_mapping_family: u8,
+    // None for family 0
+    pub channel_mapping: Option<OpusChannelMapping>,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct OpusChannelMapping {
+    pub stream_count: u8,
+    pub coupled_count: u8,
+    // one entry per channel
+    pub mapping: Vec<u8>,
+}
+
+pub fn opus_head(input: &[u8]) -> Result<OpusHead, Error> {
+    let (fixed, last) = take(input, 19)?;
+    if &fixed[..8] != b"OpusHead" {
+        return Err(Error::BadSignature { what: "'OpusHead' magic", offset: 0 });
+    }
+    if fixed[8] >> 4 != 0 {
+        return Err(Error::Unsupported { what: "Opus header version", offset: 8 });
+    }
+    let channel_count = fixed[9];
+    let channel_mapping_family = fixed[18];
+    let channel_mapping = if channel_mapping_family != 0 {
+        let (counts, rest) = take(last, 2).map_err(|e| e.shift(consumed(input, last)))?;
+        let (mapping, _) = take(rest, channel_count as usize).map_err(|e| e.shift(consumed(input, rest)))?;
+        Some(OpusChannelMapping {
+            stream_count: counts[0],
+            coupled_count: counts[1],
+            mapping: Vec::from(mapping),
+        })
+    } else {
+        None
+    };
+    Ok(OpusHead {
+        version: fixed[8],
+        channel_count,
+        pre_skip: u16::from_le_bytes([fixed[10], fixed[11]]),
+        input_sample_rate: u32::from_le_bytes([fixed[12], fixed[13], fixed[14], fixed[15]]),
+        output_gain: i16::from_le_bytes([fixed[16], fixed[17]]),
+        channel_mapping_family,
+        channel_mapping,
+    })
+}
@@ -0,0 +1,126 @@
+use crate::error::{consumed, take};
+use crate::tag::amf0::{amf_string, write_amf_string};
+use crate::tag::audio::{self, AudioTagHeader};
+use crate::tag::video::{self, VideoTagHeader};
+use crate::tag::{TAG_TYPE_AUDIO, TAG_TYPE_VIDEO};
+use crate::Error;
+
+// The data of a tag with the Filter bit set (FLV 10.1, Annex F): the media header in the clear,
+// the EncryptionTagHeader and FilterParams, then the body, which stays opaque.
+#[derive(Debug, PartialEq)]
+pub struct EncryptedData {
+    pub media_header: MediaHeader,
+    pub encryption_header: EncryptionTagHeader,
+    pub filter_params: FilterParams,
+    // the encrypted AUDIODATA, VIDEODATA or SCRIPTDATA body, after the media header
+    pub encrypted_body: Vec<u8>,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum MediaHeader {
+    // tag type 8
+    Audio(AudioTagHeader),
+    // tag type 9
+    Video(VideoTagHeader),
+    // script data has none
+    None,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct EncryptionTagHeader {
+    // always 1
+    pub num_filters: u8,
+    // "Encryption" or "SE"
+    pub filter_name: String,
+}
+
+pub const FILTER_ENCRYPTION: &str = "Encryption";
+pub const FILTER_SELECTIVE_ENCRYPTION: &str = "SE";
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum FilterParams {
+    // "Encryption": every tag is encrypted
+    Encryption { iv: [u8; 16] },
+    // "SE": only the tags with `encrypted_au` are, and only those have an IV
+    SelectiveEncryption { encrypted_au: bool, iv: Option<[u8; 16]> },
+}
+
+// `input` is the tag body and `tag_type` the low 5 bits of the TagHeader
+pub fn encrypted_data(input: &[u8], tag_type: u8) -> Result<EncryptedData, Error> {
+    let (media_header, last) = match tag_type {
+        TAG_TYPE_AUDIO => {
+            let (header, last) = audio::audio_tag_header(input)?;
+            (MediaHeader::Audio(header), last)
+        },
+        TAG_TYPE_VIDEO => {
+            let (header, last) = video::video_tag_header(input)?;
+            (MediaHeader::Video(header), last)
+        },
+        _ => (MediaHeader::None, input),
+    };
+
+    let (num_filters, rest) = take(last, 1).map_err(|e| e.shift(consumed(input, last)))?;
+    let name_offset = consumed(input, rest);
+    let (filter_name, rest) = amf_string(rest).map_err(|e| e.shift(name_offset))?;
+    let selective = match filter_name.as_str() {
+        FILTER_ENCRYPTION => false,
+        FILTER_SELECTIVE_ENCRYPTION => true,
+        _ => return Err(Error::Unsupported { what: "tag filter", offset: name_offset }),
+    };
+    let (length, rest) = take(rest, 3).map_err(|e| e.shift(consumed(input, rest)))?;
+    let length = u32::from_be_bytes([0, length[0], length[1], length[2]]) as usize;
+    let params_offset = consumed(input, rest);
+    let (params, encrypted_body) = take(rest, length).map_err(|e| e.shift(params_offset))?;
+    let filter_params = filter_params(params, selective).map_err(|e| e.shift(params_offset))?;
+
+    Ok(EncryptedData {
+        media_header,
+        encryption_header: EncryptionTagHeader {
+            num_filters: num_filters[0],
+            filter_name,
+        },
+        filter_params,
+        encrypted_body: Vec::from(encrypted_body),
+    })
+}
+
+fn filter_params(input: &[u8], selective: bool) -> Result<FilterParams, Error> {
+    if !selective {
+        let (iv, _) = take(input, 16)?;
+        return Ok(FilterParams::Encryption { iv: iv.try_into().unwrap() });
+    }
+    let (flags, last) = take(input, 1)?;
+    let encrypted_au = flags[0] & 0x80 != 0;
+    let iv = if encrypted_au {
+        let (iv, _) = take(last, 16).map_err(|e| e.shift(1))?;
+        Some(iv.try_into().unwrap())
+    } else {
+        None
+    };
+    Ok(FilterParams::SelectiveEncryption { encrypted_au, iv })
+}
+
+// The Length of the EncryptionTagHeader is computed from the FilterParams
+pub fn write_encrypted_data(out: &mut Vec<u8>, data: &EncryptedData) -> Result<(), Error> {
+    match &data.media_header {
+        MediaHeader::Audio(header) => audio::write_audio_tag_header(out, header),
+        MediaHeader::Video(header) => video::write_video_tag_header(out, header)?,
+        MediaHeader::None => {},
+    }
+    out.push(data.encryption_header.num_filters);
+    write_amf_string(out, &data.encryption_header.filter_name)?;
+    let mut params = Vec::with_capacity(17);
+    match &data.filter_params {
+        FilterParams::Encryption { iv } => params.extend_from_slice(iv),
+        FilterParams::SelectiveEncryption { encrypted_au, iv } => {
+            params.push((*encrypted_au as u8) << 7);
+            if let Some(iv) = iv {
+                params.extend_from_slice(iv);
+            }
+        },
+    }
+    out.extend_from_slice(&(params.len() as u32).to_be_bytes()[1..]);
+    out.extend_from_slice(&params);
+    out.extend_from_slice(&data.encrypted_body);
+    Ok(())
+}
@@ -1,4 +1,13 @@
+pub mod av1;
+pub mod avc;
+pub mod hevc;
+pub mod sps;
+pub mod vp9;
+
+use crate::error::take;
+use crate::tag::amf0::{self, AMFData};
 use crate::tag::video::VideoDataByFrame::VideoFramePayload;
+use crate::Error;
 
 // [bit;4]
 #[derive(Debug, PartialEq)]
@@ -32,19 +41,23 @@ pub enum CodecID {
     ScreenVideo2,
     // 7
     AVC,
+    // Enhanced FLV, FourCC 'hvc1'
+    HEVC,
+    // Enhanced FLV, FourCC 'av01'
+    AV1,
+    // Enhanced FLV, FourCC 'vp09'
+    VP9,
+    // Enhanced FLV command frames, which have no FourCC
+    None,
+    // Enhanced FLV, a FourCC this crate does not know, its packets kept as they are
+    Unknown([u8; 4]),
 }
 
-// FrameType and CodecID is a u8
-pub fn video_header(b: u8) -> Result<(FrameType, CodecID), String> {
-    let frame = (b >> 4) & 0b1111;
-    let frame = match frame {
-        1 => FrameType::Key,
-        2 => FrameType::Inter,
-        3 => FrameType::DisposableInter,
-        4 => FrameType::Generated,
-        5 => FrameType::Video,
-        _ => return Err("video_header".to_string()),
-    };
+// FrameType and CodecID is a u8.
+// Errors are reported at offset 0, the position of that byte.
+// Enhanced FLV headers, with the high bit set, are rejected: see `ex_video_header`.
+pub fn video_header(b: u8) -> Result<(FrameType, CodecID), Error> {
+    let frame = frame_type((b >> 4) & 0b1111)?;
     let codecid = b & 0b1111;
     let codecid = match codecid {
         1 => CodecID::JPEG,
@@ -54,17 +67,54 @@ pub fn video_header(b: u8) -> Result<(FrameType, CodecID), String> {
         5 => CodecID::VP6A,
         6 => CodecID::ScreenVideo2,
         7 => CodecID::AVC,
-        _ => return Err("video_header".to_string()),
+        _ => return Err(Error::InvalidCodecId { codec_id: codecid, offset: 0 }),
     };
     Ok((frame, codecid))
 }
 
+fn frame_type(frame: u8) -> Result<FrameType, Error> {
+    match frame {
+        1 => Ok(FrameType::Key),
+        2 => Ok(FrameType::Inter),
+        3 => Ok(FrameType::DisposableInter),
+        4 => Ok(FrameType::Generated),
+        5 => Ok(FrameType::Video),
+        _ => Err(Error::InvalidFrameType { frame_type: frame, offset: 0 }),
+    }
+}
+
+// IsExHeader, the high bit of the first byte of Enhanced FLV video
+const EX_HEADER: u8 = 0x80;
+
+// IsExHeader, FrameType [bit;3], VideoPacketType [bit;4] then the FourCC.
+// Command frames, other than metadata, have no FourCC: their codec is `CodecID::None`.
+// Errors are reported at their offset in `input`.
+pub fn ex_video_header(input: &[u8]) -> Result<(FrameType, VideoPacketType, CodecID), Error> {
+    let (b, _) = take(input, 1)?;
+    let frame = frame_type((b[0] >> 4) & 0b111)?;
+    let packet_type = video_packet_type(b[0] & 0b1111)?;
+    if frame == FrameType::Video && packet_type != VideoPacketType::Metadata {
+        return Ok((frame, packet_type, CodecID::None));
+    }
+    let (header, _) = take(input, 5)?;
+    let codec_id = match &header[1..5] {
+        b"hvc1" => CodecID::HEVC,
+        b"av01" => CodecID::AV1,
+        b"vp09" => CodecID::VP9,
+        _ => CodecID::Unknown([header[1], header[2], header[3], header[4]]),
+    };
+    Ok((frame, packet_type, codec_id))
+}
+
 #[derive(Debug, PartialEq)]
 pub enum VideoDataByFrame {
     VideoFramePayload(VideoPacketData),
     // 0: Start of client-side seeking video frame sequence
     // 1: End of client-side seeking video frame sequence
-    U8(u8),
+    // then whatever bytes follow it, kept as they are
+    U8(u8, Vec<u8>),
+    // Enhanced FLV command frame
+    ExCommand(ExVideoCommand),
 }
 #[derive(Debug, PartialEq)]
 pub enum VideoPacketData {
@@ -79,7 +129,9 @@ pub enum VideoPacketData {
     // codecid == 6
     ScreenV2VideoPacket(Vec<u8>),
     // codecid == 7
-    AVCVideoPacket(Vec<u8>),
+    AVCVideoPacket(AVCVideoPacket),
+    // Enhanced FLV
+    ExVideoPacket(ExVideoPacket),
 }
 
 #[derive(Debug, PartialEq)]
@@ -89,20 +141,26 @@ pub struct VideoData {
     pub video_data: VideoDataByFrame,
 }
 
-pub fn video_data(input: &[u8], size: usize) -> Result<VideoData, String> {
+pub fn video_data(input: &[u8], size: usize) -> Result<VideoData, Error> {
     if input.len() < size {
-        return Err("invalid video data length".to_string());
+        return Err(Error::Truncated { needed: size, offset: 0 });
     }
     if size < 1 {
-        return Err("video data length less than 1".to_string());
+        return Err(Error::Truncated { needed: 1, offset: 0 });
+    }
+    if input[0] & EX_HEADER != 0 {
+        return ex_video_data(&input[..size]);
     }
     let (frame_type, codec_id) = video_header(input[0])?;
 
     let data = Vec::from(&input[1..size]);
     let video_data = match frame_type {
-        FrameType::Video => VideoDataByFrame::U8(0),
+        FrameType::Video => match data.first() {
+            Some(&b) => VideoDataByFrame::U8(b, Vec::from(&data[1..])),
+            None => return Err(Error::Truncated { needed: 2, offset: 0 }),
+        },
         _ => match codec_id {
-            CodecID::JPEG => return Err("jpeg should not used".to_string()),
+            CodecID::JPEG => return Err(Error::Unsupported { what: "JPEG video", offset: 0 }),
             CodecID::Sorenson => {
                 VideoFramePayload(VideoPacketData::H263VideoPacket(data))
             },
@@ -119,8 +177,11 @@ pub fn video_data(input: &[u8], size: usize) -> Result<VideoData, String> {
                 VideoFramePayload(VideoPacketData::ScreenV2VideoPacket(data))
             },
             CodecID::AVC => {
-                VideoFramePayload(VideoPacketData::AVCVideoPacket(data))
+                let packet = avc_video_packet(&input[1..size], size - 1).map_err(|e| e.shift(1))?;
+                VideoFramePayload(VideoPacketData::AVCVideoPacket(packet))
             },
+            // only found after an ExHeader
+            CodecID::HEVC | CodecID::AV1 | CodecID::VP9 | CodecID::None | CodecID::Unknown(_) => unreachable!(),
         },
     };
 
@@ -131,6 +192,7 @@ pub fn video_data(input: &[u8], size: usize) -> Result<VideoData, String> {
     })
 }
 
+#[derive(Debug, PartialEq, Clone)]
 pub enum AVCPacketType {
     // 0
     SequenceHeader,
@@ -141,16 +203,16 @@ pub enum AVCPacketType {
     EndOfSequence,
 }
 
-fn avc_packet_type(input: u8) -> Result<AVCPacketType, String> {
+fn avc_packet_type(input: u8) -> Result<AVCPacketType, Error> {
     match input {
         0 => Ok(AVCPacketType::SequenceHeader),
         1 => Ok(AVCPacketType::NALU),
         2 => Ok(AVCPacketType::EndOfSequence),
-        _ => Err("invalid avc packet type".to_string()),
+        _ => Err(Error::InvalidAvcPacketType { packet_type: input, offset: 0 }),
     }
 }
 
-// TODO:
+#[derive(Debug, PartialEq, Clone)]
 pub struct AVCVideoPacket {
     pub avc_packet_type: AVCPacketType,
     // SI24
@@ -164,17 +226,44 @@ pub struct AVCVideoPacket {
     pub data: Vec<u8>,
 }
 
-pub fn avc_video_packet(input: &[u8], size: usize) -> Result<AVCVideoPacket, String> {
+impl AVCVideoPacket {
+    // Parses the data of a sequence header packet, None for other packet types
+    pub fn decoder_configuration_record(&self) -> Option<Result<avc::AVCDecoderConfigurationRecord, Error>> {
+        match self.avc_packet_type {
+            AVCPacketType::SequenceHeader => Some(avc::avc_decoder_configuration_record(&self.data)),
+            _ => None,
+        }
+    }
+
+    // The NAL units of a NALU packet, see `avc::nal_units`
+    pub fn nal_units(&self, length_size: usize) -> avc::NalUnits<'_> {
+        match self.avc_packet_type {
+            AVCPacketType::NALU => avc::nal_units(&self.data, length_size),
+            _ => avc::nal_units(&[], length_size),
+        }
+    }
+
+    // Whether the packet holds a slice of an IDR picture, which a decoder can start from
+    pub fn is_idr(&self, length_size: usize) -> Result<bool, Error> {
+        for nalu in self.nal_units(length_size) {
+            if nalu?.nal_unit_type == avc::NalUnitType::Idr {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+}
+
+pub fn avc_video_packet(input: &[u8], size: usize) -> Result<AVCVideoPacket, Error> {
     if input.len() < size {
-        return Err("avc video packet need more size".to_string());
+        return Err(Error::Truncated { needed: size, offset: 0 });
     }
     if size < 4 {
-        return Err("avc video packet need more than 4 length".to_string());
+        return Err(Error::Truncated { needed: 4, offset: 0 });
     }
     let avc_packet_type = avc_packet_type(input[0])?;
-    let composition_time = (input[1] as i32) << 16 |
-        (input[2] as i32) << 8 |
-        input[3] as i32;
+    // sign extend the 24 bits: B-frames have a negative offset
+    let composition_time = i32::from_be_bytes([input[1], input[2], input[3], 0]) >> 8;
 
     Ok(AVCVideoPacket{
         avc_packet_type,
@@ -183,3 +272,295 @@ pub fn avc_video_packet(input: &[u8], size: usize) -> Result<AVCVideoPacket, Str
     })
 }
 
+#[derive(Debug, PartialEq, Clone)]
+pub enum VideoPacketType {
+    // 0, the decoder configuration record of the codec
+    SequenceStart,
+    // 1, frames with a composition time for hvc1
+    CodedFrames,
+    // 2, empty
+    SequenceEnd,
+    // 3, frames with a composition time of 0
+    CodedFramesX,
+    // 4, an AMF encoded name and value, "colorInfo" for HDR
+    Metadata,
+    // 5, an MPEG-2 TS descriptor, for AV1
+    MPEG2TSSequenceStart,
+}
+
+fn video_packet_type(input: u8) -> Result<VideoPacketType, Error> {
+    match input {
+        0 => Ok(VideoPacketType::SequenceStart),
+        1 => Ok(VideoPacketType::CodedFrames),
+        2 => Ok(VideoPacketType::SequenceEnd),
+        3 => Ok(VideoPacketType::CodedFramesX),
+        4 => Ok(VideoPacketType::Metadata),
+        5 => Ok(VideoPacketType::MPEG2TSSequenceStart),
+        6 => Err(Error::Unsupported { what: "multitrack video", offset: 0 }),
+        7 => Err(Error::Unsupported { what: "video packet modifier extension", offset: 0 }),
+        _ => Err(Error::InvalidVideoPacketType { packet_type: input, offset: 0 }),
+    }
+}
+
+// The body of an Enhanced FLV video tag, after the FourCC
+#[derive(Debug, PartialEq, Clone)]
+pub struct ExVideoPacket {
+    pub packet_type: VideoPacketType,
+    // SI24
+    // if PacketType == CodedFrames and the codec is HEVC, composition time offset
+    // else 0
+    pub composition_time: i32,
+    // if PacketType == SequenceStart, the decoder configuration record of the codec
+    // else if PacketType == CodedFrames or CodedFramesX, the frames,
+    //   NALUs prefixed by their length for HEVC, OBUs for AV1, a frame for VP9
+    // else if PacketType == Metadata, AMF encoded name and value
+    // else if PacketType == MPEG2TSSequenceStart, an AV1 video descriptor
+    // else Empty
+    pub data: Vec<u8>,
+}
+
+impl ExVideoPacket {
+    // Parses the data of a sequence start packet of hvc1 video, None for other packet types
+    pub fn hevc_decoder_configuration_record(&self) -> Option<Result<hevc::HEVCDecoderConfigurationRecord, Error>> {
+        match self.packet_type {
+            VideoPacketType::SequenceStart => Some(hevc::hevc_decoder_configuration_record(&self.data)),
+            _ => None,
+        }
+    }
+
+    // Parses the data of a sequence start packet of av01 video, None for other packet types
+    pub fn av1_codec_configuration_record(&self) -> Option<Result<av1::AV1CodecConfigurationRecord, Error>> {
+        match self.packet_type {
+            VideoPacketType::SequenceStart => Some(av1::av1_codec_configuration_record(&self.data)),
+            _ => None,
+        }
+    }
+
+    // Parses the data of a sequence start packet of vp09 video, None for other packet types
+    pub fn vp_codec_configuration_record(&self) -> Option<Result<vp9::VPCodecConfigurationRecord, Error>> {
+        match self.packet_type {
+            VideoPacketType::SequenceStart => Some(vp9::vp_codec_configuration_record(&self.data)),
+            _ => None,
+        }
+    }
+
+    // Parses the name and value of a metadata packet, None for other packet types
+    pub fn metadata(&self) -> Option<Result<(String, AMFData), Error>> {
+        match self.packet_type {
+            VideoPacketType::Metadata => Some(video_metadata(&self.data)),
+            _ => None,
+        }
+    }
+}
+
+// The body of an Enhanced FLV command frame, which has no FourCC
+#[derive(Debug, PartialEq, Clone)]
+pub struct ExVideoCommand {
+    // ignored by players, kept to write the tag back
+    pub packet_type: VideoPacketType,
+    // VideoCommand
+    // 0: start seek
+    // 1: end seek
+    pub command: u8,
+    // none in the spec, kept as they are
+    pub data: Vec<u8>,
+}
+
+fn video_metadata(input: &[u8]) -> Result<(String, AMFData), Error> {
+    let (name, last) = amf0::amf_data_value(input)?;
+    let name = match name {
+        AMFData::String(name) => name,
+        _ => return Err(Error::Unsupported { what: "video metadata name", offset: 0 }),
+    };
+    let (value, _) = amf0::amf_data_value(last).map_err(|e| e.shift(input.len() - last.len()))?;
+    Ok((name, value))
+}
+
+// `input` is the whole tag body, its size already checked
+fn ex_video_data(input: &[u8]) -> Result<VideoData, Error> {
+    let (frame_type, packet_type, codec_id) = ex_video_header(input)?;
+    if codec_id == CodecID::None {
+        let (command, last) = take(&input[1..], 1).map_err(|e| e.shift(1))?;
+        return Ok(VideoData {
+            frame_type,
+            codec_id,
+            video_data: VideoDataByFrame::ExCommand(ExVideoCommand {
+                packet_type,
+                command: command[0],
+                data: Vec::from(last),
+            }),
+        });
+    }
+    let mut last = &input[5..];
+    let mut composition_time = 0;
+    if packet_type == VideoPacketType::CodedFrames && codec_id == CodecID::HEVC {
+        let (cts, rest) = take(last, 3).map_err(|e| e.shift(5))?;
+        composition_time = i32::from_be_bytes([cts[0], cts[1], cts[2], 0]) >> 8;
+        last = rest;
+    }
+    Ok(VideoData {
+        frame_type,
+        codec_id,
+        video_data: VideoFramePayload(VideoPacketData::ExVideoPacket(ExVideoPacket {
+            packet_type,
+            composition_time,
+            data: Vec::from(last),
+        })),
+    })
+}
+
+// The reverse of `video_header`.
+// For the Enhanced FLV codecs, IsExHeader and the FrameType: the VideoPacketType goes in the low 4 bits.
+pub fn write_video_header(frame_type: &FrameType, codec_id: &CodecID) -> u8 {
+    let frame: u8 = match frame_type {
+        FrameType::Key => 1,
+        FrameType::Inter => 2,
+        FrameType::DisposableInter => 3,
+        FrameType::Generated => 4,
+        FrameType::Video => 5,
+    };
+    let codecid: u8 = match codec_id {
+        CodecID::JPEG => 1,
+        CodecID::Sorenson => 2,
+        CodecID::ScreenVideo => 3,
+        CodecID::VP6 => 4,
+        CodecID::VP6A => 5,
+        CodecID::ScreenVideo2 => 6,
+        CodecID::AVC => 7,
+        CodecID::HEVC | CodecID::AV1 | CodecID::VP9 | CodecID::None | CodecID::Unknown(_) => {
+            return EX_HEADER | frame << 4
+        },
+    };
+    frame << 4 | codecid
+}
+
+pub fn write_video_data(out: &mut Vec<u8>, data: &VideoData) -> Result<(), Error> {
+    match &data.video_data {
+        VideoFramePayload(VideoPacketData::ExVideoPacket(packet)) => {
+            return write_ex_video_packet(out, &data.frame_type, &data.codec_id, packet);
+        },
+        VideoDataByFrame::ExCommand(command) => return write_ex_video_command(out, &data.frame_type, command),
+        _ => {},
+    }
+    out.push(write_video_header(&data.frame_type, &data.codec_id));
+    match &data.video_data {
+        VideoDataByFrame::U8(b, rest) => {
+            out.push(*b);
+            out.extend_from_slice(rest);
+        },
+        VideoFramePayload(VideoPacketData::H263VideoPacket(data))
+        | VideoFramePayload(VideoPacketData::ScreenVideoPacket(data))
+        | VideoFramePayload(VideoPacketData::VP6FLVVideoPacket(data))
+        | VideoFramePayload(VideoPacketData::VP6FLVAlphaVideoPacket(data))
+        | VideoFramePayload(VideoPacketData::ScreenV2VideoPacket(data)) => out.extend_from_slice(data),
+        VideoFramePayload(VideoPacketData::AVCVideoPacket(packet)) => write_avc_video_packet(out, packet)?,
+        VideoFramePayload(VideoPacketData::ExVideoPacket(_)) | VideoDataByFrame::ExCommand(_) => unreachable!(),
+    }
+    Ok(())
+}
+
+// The ExHeader byte, FourCC and body of an Enhanced FLV video tag.
+// `composition_time` is only written for the CodedFrames of HEVC, and must fit in 24 bits.
+pub fn write_ex_video_packet(out: &mut Vec<u8>, frame_type: &FrameType, codec_id: &CodecID, packet: &ExVideoPacket) -> Result<(), Error> {
+    let four_cc: &[u8; 4] = match codec_id {
+        CodecID::HEVC => b"hvc1",
+        CodecID::AV1 => b"av01",
+        CodecID::VP9 => b"vp09",
+        CodecID::Unknown(four_cc) => four_cc,
+        _ => return Err(Error::Unsupported { what: "Enhanced FLV video codec", offset: 0 }),
+    };
+    out.push(write_video_header(frame_type, codec_id) | write_video_packet_type(&packet.packet_type));
+    out.extend_from_slice(four_cc);
+    if packet.packet_type == VideoPacketType::CodedFrames && *codec_id == CodecID::HEVC {
+        if !(-0x80_0000..0x80_0000).contains(&packet.composition_time) {
+            return Err(Error::TooLarge { what: "composition time", size: packet.composition_time.unsigned_abs() as usize });
+        }
+        out.extend_from_slice(&packet.composition_time.to_be_bytes()[1..]);
+    }
+    out.extend_from_slice(&packet.data);
+    Ok(())
+}
+
+// The ExHeader byte, VideoCommand and whatever follows of an Enhanced FLV command frame
+pub fn write_ex_video_command(out: &mut Vec<u8>, frame_type: &FrameType, command: &ExVideoCommand) -> Result<(), Error> {
+    if *frame_type != FrameType::Video || command.packet_type == VideoPacketType::Metadata {
+        return Err(Error::Unsupported { what: "Enhanced FLV video command frame type", offset: 0 });
+    }
+    out.push(write_video_header(frame_type, &CodecID::None) | write_video_packet_type(&command.packet_type));
+    out.push(command.command);
+    out.extend_from_slice(&command.data);
+    Ok(())
+}
+
+fn write_video_packet_type(packet_type: &VideoPacketType) -> u8 {
+    match packet_type {
+        VideoPacketType::SequenceStart => 0,
+        VideoPacketType::CodedFrames => 1,
+        VideoPacketType::SequenceEnd => 2,
+        VideoPacketType::CodedFramesX => 3,
+        VideoPacketType::Metadata => 4,
+        VideoPacketType::MPEG2TSSequenceStart => 5,
+    }
+}
+
+// `composition_time` must fit in 24 bits
+pub fn write_avc_video_packet(out: &mut Vec<u8>, packet: &AVCVideoPacket) -> Result<(), Error> {
+    if !(-0x80_0000..0x80_0000).contains(&packet.composition_time) {
+        return Err(Error::TooLarge { what: "composition time", size: packet.composition_time.unsigned_abs() as usize });
+    }
+    out.push(write_avc_packet_type(&packet.avc_packet_type));
+    out.extend_from_slice(&packet.composition_time.to_be_bytes()[1..]);
+    out.extend_from_slice(&packet.data);
+    Ok(())
+}
+
+fn write_avc_packet_type(packet_type: &AVCPacketType) -> u8 {
+    match packet_type {
+        AVCPacketType::SequenceHeader => 0,
+        AVCPacketType::NALU => 1,
+        AVCPacketType::EndOfSequence => 2,
+    }
+}
+
+// VideoTagHeader, the part of the video data left in the clear in an encrypted tag
+#[derive(Debug, PartialEq)]
+pub struct VideoTagHeader {
+    pub frame_type: FrameType,
+    pub codec_id: CodecID,
+    // only for AVC
+    pub avc_packet_type: Option<AVCPacketType>,
+    // SI24, 0 unless AVC
+    pub composition_time: i32,
+}
+
+pub fn video_tag_header(input: &[u8]) -> Result<(VideoTagHeader, &[u8]), Error> {
+    let (b, last) = take(input, 1)?;
+    let (frame_type, codec_id) = video_header(b[0])?;
+    let (avc_packet_type, composition_time, last) = match codec_id {
+        CodecID::AVC => {
+            let (avc, last) = take(last, 4).map_err(|e| e.shift(1))?;
+            let packet_type = avc_packet_type(avc[0]).map_err(|e| e.shift(1))?;
+            (Some(packet_type), i32::from_be_bytes([avc[1], avc[2], avc[3], 0]) >> 8, last)
+        },
+        _ => (None, 0, last),
+    };
+    Ok((VideoTagHeader {
+        frame_type,
+        codec_id,
+        avc_packet_type,
+        composition_time,
+    }, last))
+}
+
+// `composition_time` must fit in 24 bits
+pub fn write_video_tag_header(out: &mut Vec<u8>, header: &VideoTagHeader) -> Result<(), Error> {
+    out.push(write_video_header(&header.frame_type, &header.codec_id));
+    if let Some(packet_type) = &header.avc_packet_type {
+        if !(-0x80_0000..0x80_0000).contains(&header.composition_time) {
+            return Err(Error::TooLarge { what: "composition time", size: header.composition_time.unsigned_abs() as usize });
+        }
+        out.push(write_avc_packet_type(packet_type));
+        out.extend_from_slice(&header.composition_time.to_be_bytes()[1..]);
+    }
+    Ok(())
+}
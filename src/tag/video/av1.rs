@@ -0,0 +1,50 @@
+use crate::error::take;
+use crate::Error;
+
+// AV1CodecConfigurationRecord, the data of an av01 sequence start (AV1 in ISOBMFF 2.3)
+#[derive(Debug, PartialEq, Clone)]
+pub struct AV1CodecConfigurationRecord {
+    // [bit;7], always 1, after a marker bit
+    pub version: u8,
+    // [bit;3]
+    pub seq_profile: u8,
+    // [bit;5]
+    pub seq_level_idx_0: u8,
+    pub seq_tier_0: bool,
+    pub high_bitdepth: bool,
+    pub twelve_bit: bool,
+    pub monochrome: bool,
+    pub chroma_subsampling_x: bool,
+    pub chroma_subsampling_y: bool,
+    // [bit;2]
+    pub chroma_sample_position: u8,
+    // in frames, None if not given
+    pub initial_presentation_delay: Option<u8>,
+    // the sequence header OBU, possibly followed by metadata OBUs
+    pub config_obus: Vec<u8>,
+}
+
+pub fn av1_codec_configuration_record(input: &[u8]) -> Result<AV1CodecConfigurationRecord, Error> {
+    let (fixed, last) = take(input, 4)?;
+    if fixed[0] != 0x81 {
+        return Err(Error::Unsupported { what: "AV1 codec configuration record version", offset: 0 });
+    }
+    Ok(AV1CodecConfigurationRecord {
+        version: fixed[0] & 0x7f,
+        seq_profile: fixed[1] >> 5,
+        seq_level_idx_0: fixed[1] & 0b1_1111,
+        seq_tier_0: fixed[2] & 0x80 != 0,
+        high_bitdepth: fixed[2] & 0x40 != 0,
+        twelve_bit: fixed[2] & 0x20 != 0,
+        monochrome: fixed[2] & 0x10 != 0,
+        chroma_subsampling_x: fixed[2] & 0x08 != 0,
+        chroma_subsampling_y: fixed[2] & 0x04 != 0,
+        chroma_sample_position: fixed[2] & 0b11,
+        initial_presentation_delay: if fixed[3] & 0x10 != 0 {
+            Some((fixed[3] & 0b1111) + 1)
+        } else {
+            None
+        },
+        config_obus: Vec::from(last),
+    })
+}
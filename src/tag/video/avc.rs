@@ -0,0 +1,230 @@
+use crate::error::{consumed, take};
+use crate::tag::video::sps::{self, SequenceParameterSet};
+use crate::Error;
+
+// AVCDecoderConfigurationRecord, the data of an AVC sequence header (ISO 14496-15)
+#[derive(Debug, PartialEq, Clone)]
+pub struct AVCDecoderConfigurationRecord {
+    // always 1
+    pub configuration_version: u8,
+    // profile_idc of the SPS
+    pub profile_indication: u8,
+    // the constraint_set flags of the SPS
+    pub profile_compatibility: u8,
+    // level_idc of the SPS
+    pub level_indication: u8,
+    // [bit;2], size in bytes of the NALU length prefix, minus one
+    pub length_size_minus_one: u8,
+    // SPS NAL units, header byte included
+    pub sequence_parameter_sets: Vec<Vec<u8>>,
+    // PPS NAL units, header byte included
+    pub picture_parameter_sets: Vec<Vec<u8>>,
+    // only for profiles 100, 110, 122 and 144, and often left out even then
+    pub high_profile: Option<AVCHighProfileExtension>,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct AVCHighProfileExtension {
+    // [bit;2], chroma_format_idc of the SPS
+    pub chroma_format: u8,
+    // [bit;3]
+    pub bit_depth_luma_minus8: u8,
+    // [bit;3]
+    pub bit_depth_chroma_minus8: u8,
+    // SPS extension NAL units
+    pub sequence_parameter_set_ext: Vec<Vec<u8>>,
+}
+
+impl AVCDecoderConfigurationRecord {
+    // Size in bytes of the length prefixing each NALU of the video packets
+    pub fn nalu_length_size(&self) -> usize {
+        self.length_size_minus_one as usize + 1
+    }
+
+    // Parses the first SPS, which gives the picture size, frame rate and aspect ratio.
+    // None if the record has no SPS.
+    pub fn sequence_parameter_set(&self) -> Option<Result<SequenceParameterSet, Error>> {
+        self.sequence_parameter_sets.first().map(|nalu| sps::sequence_parameter_set(nalu))
+    }
+}
+
+pub fn avc_decoder_configuration_record(input: &[u8]) -> Result<AVCDecoderConfigurationRecord, Error> {
+    let (fixed, last) = take(input, 6)?;
+    let profile_indication = fixed[1];
+    let (sequence_parameter_sets, last) = parameter_sets(last, (fixed[5] & 0b11111) as usize)
+        .map_err(|e| e.shift(6))?;
+    let (count, last) = take(last, 1).map_err(|e| e.shift(consumed(input, last)))?;
+    let (picture_parameter_sets, last) = parameter_sets(last, count[0] as usize)
+        .map_err(|e| e.shift(consumed(input, last)))?;
+
+    let high_profile = match profile_indication {
+        100 | 110 | 122 | 144 if !last.is_empty() => {
+            let (ext, rest) = take(last, 4).map_err(|e| e.shift(consumed(input, last)))?;
+            let (sequence_parameter_set_ext, _) = parameter_sets(rest, ext[3] as usize)
+                .map_err(|e| e.shift(consumed(input, rest)))?;
+            Some(AVCHighProfileExtension {
+                chroma_format: ext[0] & 0b11,
+                bit_depth_luma_minus8: ext[1] & 0b111,
+                bit_depth_chroma_minus8: ext[2] & 0b111,
+                sequence_parameter_set_ext,
+            })
+        },
+        _ => None,
+    };
+
+    Ok(AVCDecoderConfigurationRecord {
+        configuration_version: fixed[0],
+        profile_indication,
+        profile_compatibility: fixed[2],
+        level_indication: fixed[3],
+        length_size_minus_one: fixed[4] & 0b11,
+        sequence_parameter_sets,
+        picture_parameter_sets,
+        high_profile,
+    })
+}
+
+// `count` NAL units, each prefixed by a u16 length
+fn parameter_sets(input: &[u8], count: usize) -> Result<(Vec<Vec<u8>>, &[u8]), Error> {
+    let mut res = Vec::new();
+    let mut last = input;
+    for _ in 0..count {
+        let (length, rest) = take(last, 2).map_err(|e| e.shift(consumed(input, last)))?;
+        let length = u16::from_be_bytes([length[0], length[1]]) as usize;
+        let (nalu, rest) = take(rest, length).map_err(|e| e.shift(consumed(input, rest)))?;
+        res.push(Vec::from(nalu));
+        last = rest;
+    }
+    Ok((res, last))
+}
+
+// Removes the emulation prevention bytes of a NAL unit: 0x000003 becomes 0x0000
+pub fn rbsp(nalu: &[u8]) -> Vec<u8> {
+    let mut res = Vec::with_capacity(nalu.len());
+    let mut zeros = 0;
+    for &b in nalu {
+        if zeros >= 2 && b == 3 {
+            zeros = 0;
+            continue;
+        }
+        zeros = if b == 0 { zeros + 1 } else { 0 };
+        res.push(b);
+    }
+    res
+}
+
+// nal_unit_type of the NAL unit header (ITU-T H.264 Table 7-1)
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum NalUnitType {
+    // 1, coded slice of a non-IDR picture
+    NonIdrSlice,
+    // 2, 3, 4, coded slice data partition A, B or C
+    SliceDataPartition(u8),
+    // 5, coded slice of an IDR picture
+    Idr,
+    // 6, supplemental enhancement information
+    Sei,
+    // 7, sequence parameter set
+    Sps,
+    // 8, picture parameter set
+    Pps,
+    // 9, access unit delimiter
+    Aud,
+    // 10
+    EndOfSequence,
+    // 11
+    EndOfStream,
+    // 12, filler data
+    Filler,
+    // 13 to 31
+    Other(u8),
+    // 0, never valid
+    Unspecified,
+}
+
+impl From<u8> for NalUnitType {
+    fn from(b: u8) -> Self {
+        match b & 0b11111 {
+            0 => NalUnitType::Unspecified,
+            1 => NalUnitType::NonIdrSlice,
+            n @ 2..=4 => NalUnitType::SliceDataPartition(n),
+            5 => NalUnitType::Idr,
+            6 => NalUnitType::Sei,
+            7 => NalUnitType::Sps,
+            8 => NalUnitType::Pps,
+            9 => NalUnitType::Aud,
+            10 => NalUnitType::EndOfSequence,
+            11 => NalUnitType::EndOfStream,
+            12 => NalUnitType::Filler,
+            n => NalUnitType::Other(n),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct NalUnit<'a> {
+    // [bit;2], 0 if the NAL unit is not used for reference
+    pub nal_ref_idc: u8,
+    pub nal_unit_type: NalUnitType,
+    // the whole NAL unit, header byte included
+    pub data: &'a [u8],
+}
+
+// Splits the data of an AVC NALU packet into NAL units, each prefixed by a big endian length.
+// `length_size` is the `nalu_length_size` of the AVCDecoderConfigurationRecord.
+// Empty NAL units are skipped. Stops after the first error.
+pub struct NalUnits<'a> {
+    input: &'a [u8],
+    length_size: usize,
+    offset: usize,
+    done: bool,
+}
+
+pub fn nal_units(input: &[u8], length_size: usize) -> NalUnits<'_> {
+    NalUnits {
+        input,
+        length_size,
+        offset: 0,
+        done: false,
+    }
+}
+
+impl<'a> NalUnits<'a> {
+    fn next_nal_unit(&mut self) -> Result<Option<NalUnit<'a>>, Error> {
+        loop {
+            let input = &self.input[self.offset..];
+            if input.is_empty() {
+                return Ok(None);
+            }
+            if !(1..=4).contains(&self.length_size) {
+                return Err(Error::Unsupported { what: "NALU length size", offset: self.offset });
+            }
+            let (length, last) = take(input, self.length_size).map_err(|e| e.shift(self.offset))?;
+            let length = length.iter().fold(0usize, |n, &b| n << 8 | b as usize);
+            let (data, _) = take(last, length).map_err(|e| e.shift(self.offset + self.length_size))?;
+            self.offset += self.length_size + length;
+            if let Some(&header) = data.first() {
+                return Ok(Some(NalUnit {
+                    nal_ref_idc: header >> 5 & 0b11,
+                    nal_unit_type: NalUnitType::from(header),
+                    data,
+                }));
+            }
+        }
+    }
+}
+
+impl<'a> Iterator for NalUnits<'a> {
+    type Item = Result<NalUnit<'a>, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        let res = self.next_nal_unit();
+        if !matches!(res, Ok(Some(_))) {
+            self.done = true;
+        }
+        res.transpose()
+    }
+}
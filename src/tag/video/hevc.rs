@@ -0,0 +1,117 @@
+use crate::error::{consumed, take};
+use crate::Error;
+
+// HEVCDecoderConfigurationRecord, the data of an hvc1 sequence start (ISO 14496-15 8.3.3)
+#[derive(Debug, PartialEq, Clone)]
+pub struct HEVCDecoderConfigurationRecord {
+    // always 1
+    pub configuration_version: u8,
+    // [bit;2]
+    pub general_profile_space: u8,
+    pub general_tier_flag: bool,
+    // [bit;5]
+    pub general_profile_idc: u8,
+    pub general_profile_compatibility_flags: u32,
+    // [bit;48]
+    pub general_constraint_indicator_flags: u64,
+    pub general_level_idc: u8,
+    // [bit;12]
+    pub min_spatial_segmentation_idc: u16,
+    // [bit;2], 0: unknown, 1: slices, 2: tiles, 3: wavefront
+    pub parallelism_type: u8,
+    // [bit;2], chroma_format_idc of the SPS
+    pub chroma_format: u8,
+    // [bit;3]
+    pub bit_depth_luma_minus8: u8,
+    // [bit;3]
+    pub bit_depth_chroma_minus8: u8,
+    // frames per 256 seconds, 0 if unknown
+    pub avg_frame_rate: u16,
+    // [bit;2]
+    pub constant_frame_rate: u8,
+    // [bit;3]
+    pub num_temporal_layers: u8,
+    pub temporal_id_nested: bool,
+    // [bit;2], size in bytes of the NALU length prefix, minus one
+    pub length_size_minus_one: u8,
+    // usually the VPS, SPS and PPS, then SEI
+    pub arrays: Vec<HEVCNalUnitArray>,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct HEVCNalUnitArray {
+    // every NAL unit of this type is in the array, none in the stream
+    pub array_completeness: bool,
+    // [bit;6]
+    pub nal_unit_type: u8,
+    // header bytes included
+    pub nal_units: Vec<Vec<u8>>,
+}
+
+// nal_unit_type of the parameter sets (ITU-T H.265 Table 7-1)
+pub const HEVC_NAL_VPS: u8 = 32;
+pub const HEVC_NAL_SPS: u8 = 33;
+pub const HEVC_NAL_PPS: u8 = 34;
+
+impl HEVCDecoderConfigurationRecord {
+    // Size in bytes of the length prefixing each NALU of the coded frames
+    pub fn nalu_length_size(&self) -> usize {
+        self.length_size_minus_one as usize + 1
+    }
+
+    // The NAL units of one type, VPS, SPS or PPS for example
+    pub fn nal_units(&self, nal_unit_type: u8) -> impl Iterator<Item = &Vec<u8>> {
+        self.arrays.iter()
+            .filter(move |array| array.nal_unit_type == nal_unit_type)
+            .flat_map(|array| array.nal_units.iter())
+    }
+}
+
+pub fn hevc_decoder_configuration_record(input: &[u8]) -> Result<HEVCDecoderConfigurationRecord, Error> {
+    let (fixed, last) = take(input, 23)?;
+    let mut constraint_flags = [0; 8];
+    constraint_flags[2..].copy_from_slice(&fixed[6..12]);
+
+    let mut arrays = Vec::new();
+    let mut last = last;
+    for _ in 0..fixed[22] {
+        let (array, rest) = take(last, 3).map_err(|e| e.shift(consumed(input, last)))?;
+        let count = u16::from_be_bytes([array[1], array[2]]);
+        let mut nal_units = Vec::new();
+        let mut rest = rest;
+        for _ in 0..count {
+            let (length, tail) = take(rest, 2).map_err(|e| e.shift(consumed(input, rest)))?;
+            let length = u16::from_be_bytes([length[0], length[1]]) as usize;
+            let (nalu, tail) = take(tail, length).map_err(|e| e.shift(consumed(input, tail)))?;
+            nal_units.push(Vec::from(nalu));
+            rest = tail;
+        }
+        arrays.push(HEVCNalUnitArray {
+            array_completeness: array[0] & 0x80 != 0,
+            nal_unit_type: array[0] & 0b11_1111,
+            nal_units,
+        });
+        last = rest;
+    }
+
+    Ok(HEVCDecoderConfigurationRecord {
+        configuration_version: fixed[0],
+        general_profile_space: fixed[1] >> 6,
+        general_tier_flag: fixed[1] & 0b10_0000 != 0,
+        general_profile_idc: fixed[1] & 0b1_1111,
+        general_profile_compatibility_flags: u32::from_be_bytes([fixed[2], fixed[3], fixed[4], fixed[5]]),
+        general_constraint_indicator_flags: u64::from_be_bytes(constraint_flags),
+        general_level_idc: fixed[12],
+        min_spatial_segmentation_idc: u16::from_be_bytes([fixed[13] & 0b1111, fixed[14]]),
+        parallelism_type: fixed[15] & 0b11,
+        chroma_format: fixed[16] & 0b11,
+        bit_depth_luma_minus8: fixed[17] & 0b111,
+        bit_depth_chroma_minus8: fixed[18] & 0b111,
+        avg_frame_rate: u16::from_be_bytes([fixed[19], fixed[20]]),
+        constant_frame_rate: fixed[21] >> 6,
+        num_temporal_layers: fixed[21] >> 3 & 0b111,
+        temporal_id_nested: fixed[21] & 0b100 != 0,
+        length_size_minus_one: fixed[21] & 0b11,
+        arrays,
+    })
+}
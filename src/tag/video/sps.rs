@@ -0,0 +1,274 @@
+use crate::bits::BitReader;
+use crate::tag::video::avc::rbsp;
+use crate::Error;
+
+// The fields of an H.264 sequence parameter set (ITU-T H.264 7.3.2.1) that describe the picture.
+// Errors are reported at their offset in the SPS with emulation prevention bytes removed.
+#[derive(Debug, PartialEq, Clone)]
+pub struct SequenceParameterSet {
+    pub profile_idc: u8,
+    // constraint_set0_flag to constraint_set5_flag, then 2 reserved bits
+    pub constraint_set_flags: u8,
+    pub level_idc: u8,
+    pub seq_parameter_set_id: u32,
+    // 0: monochrome, 1: 4:2:0, 2: 4:2:2, 3: 4:4:4
+    pub chroma_format_idc: u32,
+    pub separate_colour_plane: bool,
+    pub bit_depth_luma: u32,
+    pub bit_depth_chroma: u32,
+    pub max_num_ref_frames: u32,
+    // false for interlaced video, coded as fields or MBAFF
+    pub frame_mbs_only: bool,
+    // luma samples, before cropping
+    pub coded_width: u32,
+    pub coded_height: u32,
+    // luma samples removed from each side: left, right, top, bottom
+    pub crop: [u32; 4],
+    // None if the VUI does not say
+    pub sample_aspect_ratio: Option<(u16, u16)>,
+    pub timing: Option<TimingInfo>,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct TimingInfo {
+    pub num_units_in_tick: u32,
+    pub time_scale: u32,
+    pub fixed_frame_rate: bool,
+}
+
+// Table E-1, indexed by aspect_ratio_idc
+const SAMPLE_ASPECT_RATIOS: [(u16, u16); 17] = [
+    (0, 0),
+    (1, 1),
+    (12, 11),
+    (10, 11),
+    (16, 11),
+    (40, 33),
+    (24, 11),
+    (20, 11),
+    (32, 11),
+    (80, 33),
+    (18, 11),
+    (15, 11),
+    (64, 33),
+    (160, 99),
+    (4, 3),
+    (3, 2),
+    (2, 1),
+];
+const EXTENDED_SAR: u32 = 255;
+
+impl SequenceParameterSet {
+    // Width of the displayed picture, after cropping
+    pub fn width(&self) -> u32 {
+        let (crop_unit_x, _) = self.crop_units();
+        self.coded_width.saturating_sub(crop_unit_x.saturating_mul(self.crop[0].saturating_add(self.crop[1])))
+    }
+
+    // Height of the displayed picture, after cropping
+    pub fn height(&self) -> u32 {
+        let (_, crop_unit_y) = self.crop_units();
+        self.coded_height.saturating_sub(crop_unit_y.saturating_mul(self.crop[2].saturating_add(self.crop[3])))
+    }
+
+    // Frames per second, from the VUI timing info.
+    // A frame lasts two ticks, one for each field.
+    pub fn frame_rate(&self) -> Option<f64> {
+        match &self.timing {
+            Some(timing) if timing.num_units_in_tick > 0 => {
+                Some(timing.time_scale as f64 / (2.0 * timing.num_units_in_tick as f64))
+            },
+            _ => None,
+        }
+    }
+
+    // CropUnitX and CropUnitY, equations 7-19 to 7-22
+    fn crop_units(&self) -> (u32, u32) {
+        let field = if self.frame_mbs_only { 1 } else { 2 };
+        if self.separate_colour_plane || self.chroma_format_idc == 0 {
+            return (1, field);
+        }
+        let (sub_width_c, sub_height_c) = match self.chroma_format_idc {
+            1 => (2, 2),
+            2 => (2, 1),
+            _ => (1, 1),
+        };
+        (sub_width_c, sub_height_c * field)
+    }
+}
+
+// `input` is a whole SPS NAL unit, header byte included
+pub fn sequence_parameter_set(input: &[u8]) -> Result<SequenceParameterSet, Error> {
+    let data = rbsp(input);
+    let mut r = BitReader::new(&data);
+    // forbidden_zero_bit, nal_ref_idc, nal_unit_type
+    r.skip_bits(8)?;
+    let profile_idc = r.read_bits(8)? as u8;
+    let constraint_set_flags = r.read_bits(8)? as u8;
+    let level_idc = r.read_bits(8)? as u8;
+    let seq_parameter_set_id = r.read_ue()?;
+
+    let mut chroma_format_idc = 1;
+    let mut separate_colour_plane = false;
+    let mut bit_depth_luma = 8;
+    let mut bit_depth_chroma = 8;
+    if matches!(profile_idc, 100 | 110 | 122 | 244 | 44 | 83 | 86 | 118 | 128 | 138 | 139 | 134 | 135) {
+        chroma_format_idc = r.read_ue()?;
+        if chroma_format_idc == 3 {
+            separate_colour_plane = r.read_bit()?;
+        }
+        bit_depth_luma = r.read_ue()?.saturating_add(8);
+        bit_depth_chroma = r.read_ue()?.saturating_add(8);
+        // qpprime_y_zero_transform_bypass_flag
+        r.skip_bits(1)?;
+        let seq_scaling_matrix_present = r.read_bit()?;
+        if seq_scaling_matrix_present {
+            let lists = if chroma_format_idc != 3 { 8 } else { 12 };
+            for i in 0..lists {
+                let seq_scaling_list_present = r.read_bit()?;
+                if seq_scaling_list_present {
+                    skip_scaling_list(&mut r, if i < 6 { 16 } else { 64 })?;
+                }
+            }
+        }
+    }
+
+    // log2_max_frame_num_minus4
+    r.read_ue()?;
+    let pic_order_cnt_type = r.read_ue()?;
+    if pic_order_cnt_type == 0 {
+        // log2_max_pic_order_cnt_lsb_minus4
+        r.read_ue()?;
+    } else if pic_order_cnt_type == 1 {
+        // delta_pic_order_always_zero_flag, offset_for_non_ref_pic, offset_for_top_to_bottom_field
+        r.skip_bits(1)?;
+        r.read_se()?;
+        r.read_se()?;
+        let num_ref_frames_in_pic_order_cnt_cycle = r.read_ue()?;
+        for _ in 0..num_ref_frames_in_pic_order_cnt_cycle {
+            r.read_se()?;
+        }
+    }
+    let max_num_ref_frames = r.read_ue()?;
+    // gaps_in_frame_num_value_allowed_flag
+    r.skip_bits(1)?;
+    let pic_width_in_mbs = r.read_ue()? as u64 + 1;
+    let pic_height_in_map_units = r.read_ue()? as u64 + 1;
+    let frame_mbs_only = r.read_bit()?;
+    if !frame_mbs_only {
+        // mb_adaptive_frame_field_flag
+        r.skip_bits(1)?;
+    }
+    // direct_8x8_inference_flag
+    r.skip_bits(1)?;
+    let mut crop = [0; 4];
+    let frame_cropping = r.read_bit()?;
+    if frame_cropping {
+        for offset in crop.iter_mut() {
+            *offset = r.read_ue()?;
+        }
+    }
+
+    let vui_parameters_present = r.read_bit()?;
+    let vui = if vui_parameters_present {
+        vui_parameters(&mut r)?
+    } else {
+        Vui::default()
+    };
+
+    let field = if frame_mbs_only { 1 } else { 2 };
+    Ok(SequenceParameterSet {
+        profile_idc,
+        constraint_set_flags,
+        level_idc,
+        seq_parameter_set_id,
+        chroma_format_idc,
+        separate_colour_plane,
+        bit_depth_luma,
+        bit_depth_chroma,
+        max_num_ref_frames,
+        frame_mbs_only,
+        coded_width: (pic_width_in_mbs * 16).min(u32::MAX as u64) as u32,
+        coded_height: (pic_height_in_map_units * 16 * field).min(u32::MAX as u64) as u32,
+        crop,
+        sample_aspect_ratio: vui.sample_aspect_ratio,
+        timing: vui.timing,
+    })
+}
+
+// Only the values are skipped, the lists are not needed to know the picture size
+fn skip_scaling_list(r: &mut BitReader, size: usize) -> Result<(), Error> {
+    let mut last_scale: i32 = 8;
+    let mut next_scale: i32 = 8;
+    for _ in 0..size {
+        if next_scale != 0 {
+            let delta_scale = r.read_se()?;
+            next_scale = (last_scale as i64 + delta_scale as i64).rem_euclid(256) as i32;
+        }
+        if next_scale != 0 {
+            last_scale = next_scale;
+        }
+    }
+    Ok(())
+}
+
+#[derive(Default)]
+struct Vui {
+    sample_aspect_ratio: Option<(u16, u16)>,
+    timing: Option<TimingInfo>,
+}
+
+// Annex E.1.1, up to the timing info
+fn vui_parameters(r: &mut BitReader) -> Result<Vui, Error> {
+    let mut sample_aspect_ratio = None;
+    let aspect_ratio_info_present = r.read_bit()?;
+    if aspect_ratio_info_present {
+        let aspect_ratio_idc = r.read_bits(8)?;
+        if aspect_ratio_idc == EXTENDED_SAR {
+            let sar_width = r.read_bits(16)? as u16;
+            let sar_height = r.read_bits(16)? as u16;
+            sample_aspect_ratio = Some((sar_width, sar_height));
+        } else if let Some(&sar) = SAMPLE_ASPECT_RATIOS.get(aspect_ratio_idc as usize) {
+            // 0 is "unspecified"
+            if aspect_ratio_idc != 0 {
+                sample_aspect_ratio = Some(sar);
+            }
+        }
+    }
+
+    let overscan_info_present = r.read_bit()?;
+    if overscan_info_present {
+        // overscan_appropriate_flag
+        r.skip_bits(1)?;
+    }
+    let video_signal_type_present = r.read_bit()?;
+    if video_signal_type_present {
+        // video_format, video_full_range_flag
+        r.skip_bits(4)?;
+        let colour_description_present = r.read_bit()?;
+        if colour_description_present {
+            // colour_primaries, transfer_characteristics, matrix_coefficients
+            r.skip_bits(24)?;
+        }
+    }
+    let chroma_loc_info_present = r.read_bit()?;
+    if chroma_loc_info_present {
+        // chroma_sample_loc_type_top_field, chroma_sample_loc_type_bottom_field
+        r.read_ue()?;
+        r.read_ue()?;
+    }
+
+    let mut timing = None;
+    let timing_info_present = r.read_bit()?;
+    if timing_info_present {
+        timing = Some(TimingInfo {
+            num_units_in_tick: r.read_bits(32)?,
+            time_scale: r.read_bits(32)?,
+            fixed_frame_rate: r.read_bit()?,
+        });
+    }
+    Ok(Vui {
+        sample_aspect_ratio,
+        timing,
+    })
+}
@@ -0,0 +1,42 @@
+use crate::error::{consumed, take};
+use crate::Error;
+
+// VPCodecConfigurationRecord, the data of a vp09 sequence start (VP Codec ISOBMFF Binding 2.3).
+// It is stored with the version and flags of its vpcC box, like the MP4 muxers write it.
+#[derive(Debug, PartialEq, Clone)]
+pub struct VPCodecConfigurationRecord {
+    pub profile: u8,
+    pub level: u8,
+    // [bit;4]
+    pub bit_depth: u8,
+    // [bit;3], 0: 4:2:0 vertical, 1: 4:2:0 colocated, 2: 4:2:2, 3: 4:4:4
+    pub chroma_subsampling: u8,
+    pub video_full_range: bool,
+    // ISO 23001-8 code points
+    pub colour_primaries: u8,
+    pub transfer_characteristics: u8,
+    pub matrix_coefficients: u8,
+    // always empty for VP9
+    pub codec_initialization_data: Vec<u8>,
+}
+
+pub fn vp_codec_configuration_record(input: &[u8]) -> Result<VPCodecConfigurationRecord, Error> {
+    let (fixed, last) = take(input, 12)?;
+    // version 0 was a draft with another layout
+    if fixed[0] != 1 {
+        return Err(Error::Unsupported { what: "VP codec configuration record version", offset: 0 });
+    }
+    let size = u16::from_be_bytes([fixed[10], fixed[11]]) as usize;
+    let (codec_initialization_data, _) = take(last, size).map_err(|e| e.shift(consumed(input, last)))?;
+    Ok(VPCodecConfigurationRecord {
+        profile: fixed[4],
+        level: fixed[5],
+        bit_depth: fixed[6] >> 4,
+        chroma_subsampling: fixed[6] >> 1 & 0b111,
+        video_full_range: fixed[6] & 1 != 0,
+        colour_primaries: fixed[7],
+        transfer_characteristics: fixed[8],
+        matrix_coefficients: fixed[9],
+        codec_initialization_data: Vec::from(codec_initialization_data),
+    })
+}
@@ -0,0 +1,73 @@
+use std::io::Write;
+
+use crate::header::{self, FLVHeader};
+use crate::tag::{self, Tag, TagHeader};
+use crate::Error;
+
+// Writes an FLV file to any byte sink: the header first, then one tag at a time,
+// each followed by its PreviousTagSize.
+pub struct FlvWriter<W: Write> {
+    inner: W,
+    // the tag being written, reused between tags
+    buf: Vec<u8>,
+}
+
+impl<W: Write> FlvWriter<W> {
+    // Writes the FLV header and PreviousTagSize0
+    pub fn new(mut inner: W, header: &FLVHeader) -> Result<Self, Error> {
+        let mut buf = Vec::new();
+        header::write_flv_header(&mut buf, header);
+        buf.extend_from_slice(&0u32.to_be_bytes());
+        inner.write_all(&buf)?;
+        Ok(FlvWriter {
+            inner,
+            buf,
+        })
+    }
+
+    // Encodes `tag.data`, its `data_size` is computed rather than taken from `tag.header`
+    pub fn write_tag(&mut self, tag: &Tag) -> Result<(), Error> {
+        self.buf.clear();
+        tag::write_tag(&mut self.buf, tag)?;
+        self.finish_tag()
+    }
+
+    // Writes a tag whose data is already encoded, and not encrypted
+    pub fn write_raw_tag(&mut self, tag_type: u8, timestamp: u32, data: &[u8]) -> Result<(), Error> {
+        if data.len() > 0xff_ffff {
+            return Err(Error::TooLarge { what: "tag data", size: data.len() });
+        }
+        self.buf.clear();
+        tag::write_tag_header(&mut self.buf, &TagHeader {
+            reserved: 0,
+            filter: false,
+            tag_type,
+            data_size: data.len() as u32,
+            timestamp,
+            stream_id: 0,
+        })?;
+        self.buf.extend_from_slice(data);
+        self.finish_tag()
+    }
+
+    // Appends the PreviousTagSize to the tag in `buf`, then writes both
+    fn finish_tag(&mut self) -> Result<(), Error> {
+        let tag_size = self.buf.len() as u32;
+        self.buf.extend_from_slice(&tag_size.to_be_bytes());
+        self.inner.write_all(&self.buf)?;
+        Ok(())
+    }
+
+    pub fn flush(&mut self) -> Result<(), Error> {
+        self.inner.flush()?;
+        Ok(())
+    }
+
+    pub fn get_ref(&self) -> &W {
+        &self.inner
+    }
+
+    pub fn into_inner(self) -> W {
+        self.inner
+    }
+}